@@ -0,0 +1,47 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use reqwest::blocking::Response;
+
+use crate::config::BodyStoreFormat;
+
+/// File name for the saved body of a URL: the URL with everything but ASCII
+/// letters, digits, `-` and `_` replaced by `_`, cut to 100 characters,
+/// plus a hash of the full URL so distinct URLs never share a file
+pub fn file_name(url: &str) -> String {
+    let readable: String = url.chars()
+        .take(100)
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    format!("{}-{:08x}.body", readable, fnv1a(url.as_bytes()))
+}
+
+/// 32-bit FNV-1a, stable across platforms and releases unlike std's hashers
+pub(crate) fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, byte| (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193))
+}
+
+/// Status line and headers of a response as they would appear on the wire
+pub fn response_head(response: &Response) -> String {
+    let mut head = format!("{:?} {}\r\n", response.version(), response.status());
+    for (name, value) in response.headers() {
+        head.push_str(&format!("{}: {}\r\n", name, String::from_utf8_lossy(value.as_bytes())));
+    }
+    head.push_str("\r\n");
+    head
+}
+
+/// Write a response body into `dir`, preceded by `head` in `with-headers`
+/// format. Returns the path written
+pub fn save(dir: &Path, url: &str, format: BodyStoreFormat, head: &str, body: &[u8]) -> io::Result<PathBuf> {
+    let path = dir.join(file_name(url));
+    let mut file = fs::File::create(&path)?;
+    if matches!(format, BodyStoreFormat::WithHeaders) {
+        file.write_all(head.as_bytes())?;
+    }
+    file.write_all(body)?;
+    Ok(path)
+}
@@ -0,0 +1,1431 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Read},
+    net::{IpAddr, SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use reqwest::{
+    blocking::{Client, Response},
+    header::{
+        HeaderMap, CONNECTION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_SECURITY_POLICY, CONTENT_TYPE, DATE,
+        LAST_MODIFIED, X_FRAME_OPTIONS,
+    },
+    redirect, Method, Url, Version as HttpVersion,
+};
+use chrono::{DateTime, Utc};
+use scraper::Html;
+use semver::Version;
+
+use crate::{
+    bodies, dns, html, icmp, limits, smtp, websocket,
+    random::Rng,
+    soft404::{self, Fingerprint},
+    ssh,
+    status::{CachePolicy, CookieSecurity, ExpectCt, RequestInfo},
+    summary::percentile,
+    throttle::{RampGate, TokenBucket},
+    tls, Config, LatencyStats, ProbeResult, TimingBreakdown, WebsiteStatus,
+};
+
+/// HTML checks parse at most this much of a page
+const MAX_HTML_BYTES: u64 = 1024 * 1024;
+/// Limit instead of `MAX_HTML_BYTES` with `check_sri`, since scripts are
+/// often loaded at the end of large pages
+const MAX_SRI_HTML_BYTES: u64 = 2 * 1024 * 1024;
+/// Start of the error of a request cut off by an HTTP/2 GOAWAY
+const HTTP2_GOAWAY: &str = "HTTP/2 GOAWAY";
+/// Shortest time between two doublings of a growing worker pool, so
+/// growth follows a backlog that lasts rather than the initial burst
+const GROWTH_INTERVAL: Duration = Duration::from_millis(500);
+/// Retries for GOAWAY errors with `retry_on_http2_goaway`, not counting
+/// `retries`
+const MAX_GOAWAY_RETRIES: u32 = 2;
+
+/// State shared by all workers for the duration of a scan
+#[derive(Default)]
+pub(crate) struct ScanState {
+    /// Cumulative time spent checking each host, including retries
+    host_time: Mutex<HashMap<String, Duration>>,
+    /// First resolved address of each host, `None` when resolution failed
+    resolved: Mutex<HashMap<String, Option<IpAddr>>>,
+    /// Checks currently running against each IP address
+    ip_in_flight: Mutex<HashMap<IpAddr, usize>>,
+    /// Signalled whenever a check releases its IP slot
+    ip_released: Condvar,
+    /// First https URL of each host that failed certificate verification
+    cert_failures: Mutex<HashMap<String, String>>,
+    /// Answer of each origin for a missing path, `None` when it was not a 2xx
+    missing_pages: Mutex<HashMap<String, Option<(String, Fingerprint)>>>,
+    rng: Rng,
+}
+
+impl ScanState {
+    /// Resolve a host once per scan
+    fn resolve(&self, host: &str) -> Option<IpAddr> {
+        if let Some(ip) = self.resolved.lock().unwrap().get(host) {
+            return *ip;
+        }
+        let ip = (host, 0).to_socket_addrs().ok().and_then(|mut addrs| addrs.next()).map(|a| a.ip());
+        self.resolved.lock().unwrap().insert(host.to_string(), ip);
+        ip
+    }
+
+    /// Fetch a missing page once per origin
+    fn missing_page(&self, client: &Client, config: &Config, url: &str) -> Option<(String, Fingerprint)> {
+        let origin = Url::parse(url).ok()?.origin().ascii_serialization();
+        if let Some(page) = self.missing_pages.lock().unwrap().get(&origin) {
+            return page.clone();
+        }
+        let token = self.rng.value(&format!("missing-page {}", origin)) as u32;
+        let page = soft404::missing_page(client, config, url, token);
+        self.missing_pages.lock().unwrap().insert(origin, page.clone());
+        page
+    }
+
+    /// Wait until fewer than `limit` checks are running against `ip`, then
+    /// take a slot that is released when the guard drops
+    fn acquire_ip(&self, ip: IpAddr, limit: usize) -> IpSlot<'_> {
+        let mut in_flight = self.ip_in_flight.lock().unwrap();
+        while in_flight.get(&ip).copied().unwrap_or(0) >= limit {
+            in_flight = self.ip_released.wait(in_flight).unwrap();
+        }
+        *in_flight.entry(ip).or_default() += 1;
+        IpSlot { state: self, ip }
+    }
+}
+
+/// A running check's share of the per-IP concurrency limit
+struct IpSlot<'a> {
+    state: &'a ScanState,
+    ip: IpAddr,
+}
+
+impl Drop for IpSlot<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = self.state.ip_in_flight.lock().unwrap();
+        if let Some(count) = in_flight.get_mut(&self.ip) {
+            *count -= 1;
+        }
+        self.state.ip_released.notify_all();
+    }
+}
+
+/// Host part of a target, used to group URLs that hit the same server
+pub(crate) fn target_host(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_else(|| {
+            let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+            rest.split('/').next().unwrap_or(rest).to_string()
+        })
+}
+
+/// HTTP clients of a scan: one per `config.domain_timeouts` entry, with
+/// that timeout, and one with `config.timeout` for every other host
+pub(crate) struct Clients {
+    default: Client,
+    domains: HashMap<String, Client>,
+    /// Answers of `config.dns_servers` for the hosts of HTTP targets, which
+    /// the clients connect to instead of resolving them
+    dns_answers: HashMap<String, Result<dns::Answer, String>>,
+}
+
+impl Clients {
+    /// The client for requests to `host`
+    pub(crate) fn for_host(&self, host: &str, config: &Config) -> &Client {
+        config.domain_timeout(host)
+            .and_then(|domain| self.domains.get(domain))
+            .unwrap_or(&self.default)
+    }
+}
+
+/// Validate the environment for a scan of `urls` and build the HTTP clients
+/// and the per-scan copy of the config that workers share
+pub(crate) fn prepare(urls: &[String], config: &Config) -> Result<(Clients, Config), String> {
+    // Raw ICMP sockets need elevated privileges, fail before any check runs
+    if urls.iter().any(|url| url.starts_with("icmp://")) {
+        icmp::check_privileges()?;
+    }
+
+    // Binding fails later with an opaque connect error, check it up front
+    if let Some(ip) = config.source_ip {
+        UdpSocket::bind((ip, 0))
+            .map_err(|e| format!("Source IP {} is not assigned to a local interface: {}", ip, e))?;
+    }
+
+    let dns_answers = resolve_hosts(urls, config);
+    let mut domains = HashMap::new();
+    for (domain, secs) in &config.domain_timeouts {
+        domains.insert(domain.clone(), build_client(config, Duration::from_secs(*secs), &dns_answers)?);
+    }
+    let default = build_client(config, config.timeout, &dns_answers)?;
+    let clients = Clients { default, domains, dns_answers };
+    let mut config = Config {
+        retry_bucket: config.retry_rate.map(|rate| Arc::new(TokenBucket::new(rate))),
+        ..config.clone()
+    };
+    if config.disable_compression {
+        config.headers.retain(|(name, _)| !name.eq_ignore_ascii_case("accept-encoding"));
+    }
+    Ok((clients, config))
+}
+
+/// Resolve the host of every HTTP target with `config.dns_servers`, on up
+/// to `config.workers` threads. Nothing is resolved without custom servers
+fn resolve_hosts(urls: &[String], config: &Config) -> HashMap<String, Result<dns::Answer, String>> {
+    if config.dns_servers.is_empty() {
+        return HashMap::new();
+    }
+    let mut hosts: Vec<String> = urls.iter()
+        .filter(|url| url.starts_with("http://") || url.starts_with("https://"))
+        .filter_map(|url| Url::parse(url).ok()?.domain().map(str::to_string))
+        .collect();
+    hosts.sort();
+    hosts.dedup();
+
+    let per_thread = hosts.len().div_ceil(config.workers.max(1)).max(1);
+    thread::scope(|scope| {
+        let threads: Vec<_> = hosts.chunks(per_thread)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk.iter()
+                        .map(|host| (host.clone(), dns::resolve(&config.dns_servers, host, config.timeout)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        threads.into_iter().flat_map(|thread| thread.join().unwrap()).collect()
+    })
+}
+
+/// Create an HTTP client with a timeout
+fn build_client(
+    config: &Config,
+    timeout: Duration,
+    dns_answers: &HashMap<String, Result<dns::Answer, String>>,
+) -> Result<Client, String> {
+    // Redirects must not lead outside the configured host scope either
+    let scope = config.clone();
+    let redirects = redirect::Policy::custom(move |attempt| {
+        let rejection = scope.host_rejection(attempt.url().host_str().unwrap_or_default());
+        match rejection {
+            Some(reason) => attempt.error(format!("redirect blocked: {}", reason)),
+            None if attempt.previous().len() >= 10 => attempt.error("too many redirects"),
+            None => attempt.follow(),
+        }
+    });
+
+    let mut builder = Client::builder()
+        .timeout(timeout)
+        .redirect(redirects)
+        .local_address(config.source_ip);
+    if config.disable_compression {
+        builder = builder.no_gzip().no_brotli().no_deflate();
+    }
+    // The port of the URL replaces the one given here
+    for (host, answer) in dns_answers {
+        if let Ok(answer) = answer {
+            let addresses: Vec<SocketAddr> = answer.addresses.iter().map(|ip| SocketAddr::new(*ip, 0)).collect();
+            builder = builder.resolve_to_addrs(host, &addresses);
+        }
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+/// Check every target on a pool of `config.workers` threads, or one that
+/// grows up to `config.max_workers` while URLs wait.
+///
+/// `on_result` is called on the calling thread for each result in
+/// completion order, or input order with `config.ordered_output`; all
+/// results are also returned, in the same order, once the scan is done.
+pub fn run<F>(urls: Vec<String>, config: &Config, mut on_result: F) -> Result<Vec<WebsiteStatus>, String>
+where
+    F: FnMut(&WebsiteStatus),
+{
+    let (clients, config) = prepare(&urls, config)?;
+    let clients = Arc::new(clients);
+    let config = Arc::new(config);
+    let state = Arc::new(ScanState { rng: Rng::new(config.seed), ..ScanState::default() });
+    let ramp = Arc::new(config.ramp_up.map(|ramp| RampGate::new(ramp, config.workers)));
+
+    // Create channel for communication between caller and workers. Every
+    // URL travels with its input position so results can be put back in order
+    let (sender, receiver) = mpsc::channel::<(usize, String)>();
+    let receiver = Arc::new(Mutex::new(receiver));
+    let (result_sender, result_receiver) = mpsc::channel::<(usize, WebsiteStatus)>();
+
+    // URLs sent but not yet picked up by a worker. All are sent before the
+    // first one can be taken, so the count starts at the total
+    let waiting = Arc::new(AtomicUsize::new(urls.len()));
+
+    let spawn_worker = |worker: usize, result_sender: mpsc::Sender<(usize, WebsiteStatus)>| {
+        let clients = Arc::clone(&clients);
+        let receiver = Arc::clone(&receiver);
+        let config = Arc::clone(&config);
+        let state = Arc::clone(&state);
+        let ramp = Arc::clone(&ramp);
+        let waiting = Arc::clone(&waiting);
+
+        thread::spawn(move || {
+            while let Ok((index, url)) = {
+                let receiver = receiver.lock().unwrap();
+                receiver.recv()
+            } {
+                waiting.fetch_sub(1, Ordering::Relaxed);
+                let _permit = ramp.as_ref().as_ref().map(RampGate::acquire);
+                let mut status = check_target(&clients, &url, &config, &state);
+                status.worker = worker;
+
+                // Send result to the caller
+                result_sender.send((index, status)).unwrap();
+            }
+        })
+    };
+
+    // Create worker threads
+    let mut handles = Vec::with_capacity(config.max_workers.unwrap_or(config.workers));
+    for worker in 0..config.workers {
+        handles.push(spawn_worker(worker, result_sender.clone()));
+    }
+
+    // Send URLs to workers
+    for (index, url) in urls.into_iter().enumerate() {
+        sender.send((index, url)).unwrap_or_else(|e| {
+            eprintln!("Failed to send URL to worker: {}", e);
+        });
+    }
+
+    // Close the URL channel to signal workers to finish once it is empty.
+    // The result channel closes once the last worker exits; a growing pool
+    // keeps a sender for new workers until it stops growing
+    drop(sender);
+    let mut result_sender = config.max_workers.is_some().then_some(result_sender);
+    let mut last_growth = Instant::now();
+
+    // Hand out results as they arrive, or with `ordered_output` hold early
+    // ones back until everything before them is done
+    let mut all_results = Vec::new();
+    let mut pending = BTreeMap::new();
+    loop {
+        let received = match result_receiver.recv_timeout(GROWTH_INTERVAL) {
+            Ok(received) => Some(received),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        // Double the pool while more URLs wait than there are workers.
+        // Workers exit on their own once the queue is empty
+        if let (Some(sender), Some(max)) = (&result_sender, config.max_workers) {
+            let backlog = waiting.load(Ordering::Relaxed);
+            if backlog == 0 || handles.len() >= max {
+                result_sender = None;
+            } else if backlog > handles.len() && last_growth.elapsed() >= GROWTH_INTERVAL {
+                for _ in 0..handles.len().min(max - handles.len()) {
+                    handles.push(spawn_worker(handles.len(), sender.clone()));
+                }
+                last_growth = Instant::now();
+            }
+        }
+
+        let Some((index, status)) = received else {
+            continue;
+        };
+        if !config.ordered_output {
+            on_result(&status);
+            all_results.push(status);
+            continue;
+        }
+        pending.insert(index, status);
+        while let Some(status) = pending.remove(&all_results.len()) {
+            on_result(&status);
+            all_results.push(status);
+        }
+    }
+    // Only left over if a worker died mid-check and left a gap
+    for status in pending.into_values() {
+        on_result(&status);
+        all_results.push(status);
+    }
+
+    // Wait for all worker threads to complete
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    Ok(all_results)
+}
+
+/// Check a single target and fill in every result field
+pub(crate) fn check_target(clients: &Clients, url: &str, config: &Config, state: &ScanState) -> WebsiteStatus {
+    // A `retries=` annotation replaces the global count for its URL
+    let annotated;
+    let config = match config.url_retries.get(url) {
+        Some(&retries) => {
+            annotated = Config { retries, ..config.clone() };
+            &annotated
+        }
+        None => config,
+    };
+    let mut status = WebsiteStatus::new(url);
+    let host = target_host(url);
+    let client = clients.for_host(&host, config);
+
+    if let Some(reason) = config.host_rejection(&host) {
+        status.action_status = Err(format!("skipped: {}", reason));
+        return status;
+    }
+
+    if let Some(budget) = config.host_time_budget {
+        let spent = state.host_time.lock().unwrap().get(&host).copied().unwrap_or_default();
+        if spent >= budget {
+            status.action_status = Err(format!(
+                "skipped: host time budget of {}s exceeded for {}",
+                budget.as_secs(),
+                host
+            ));
+            return status;
+        }
+    }
+
+    if config.abort_on_cert_error
+        && url.starts_with("https://")
+        && let Some(first) = state.cert_failures.lock().unwrap().get(&host)
+    {
+        status.action_status = Err(format!(
+            "skipped: certificate error on {} already reported for {}",
+            host, first
+        ));
+        return status;
+    }
+
+    // Hosts of HTTP targets that the custom DNS servers could not resolve
+    // are not looked up again by the system resolver
+    let answer = if url.starts_with("http://") || url.starts_with("https://") {
+        clients.dns_answers.get(&host)
+    } else {
+        None
+    };
+    match answer {
+        Some(Err(e)) => {
+            status.action_status = Err(e.clone());
+            return status;
+        }
+        Some(Ok(answer)) if config.verbose => status.dns_server = Some(dns::resolver_name(&answer.server)),
+        _ => {}
+    }
+
+    // DNS checks query the resolvers, not the host itself
+    let _slot = match config.per_ip_limit {
+        Some(limit) if !url.starts_with("dns://") => {
+            let ip = match answer {
+                Some(Ok(answer)) => answer.addresses.first().copied(),
+                _ => state.resolve(&host),
+            };
+            ip.map(|ip| state.acquire_ip(ip, limit))
+        }
+        _ => None,
+    };
+
+    let start = Instant::now();
+    match url.split_once("://").map(|(scheme, _)| scheme) {
+        Some("icmp") => check_icmp(&mut status, config),
+        Some("smtp") => check_smtp(&mut status, config),
+        Some("ssh") => check_ssh(&mut status, config),
+        Some("dns") => check_dns(&mut status, config),
+        Some("ws" | "wss") => check_websocket(&mut status, config),
+        _ if !config.probe_paths.is_empty() && is_host_only(url) => check_probes(client, &mut status, config),
+        _ if config.request_count > 1 => check_http_samples(client, &mut status, config),
+        _ => check_http(client, &mut status, config),
+    }
+    if config.detect_soft_404
+        && let Some(fingerprint) = status.body_fingerprint
+        && status.action_status.as_ref().is_ok_and(|code| (200..300).contains(code))
+        && let Some((missing, page)) = state.missing_page(client, config, url)
+        && let Some(resemblance) = fingerprint.resemblance(&page)
+    {
+        status.possible_soft_404 = Some(format!("body {} {}", resemblance, missing));
+    }
+    if config.check_trailing_slash && (url.starts_with("http://") || url.starts_with("https://")) {
+        check_trailing_slash(client, &mut status, config);
+    }
+    if let Some(baseline) = &config.size_baseline
+        && (url.starts_with("http://") || url.starts_with("https://"))
+    {
+        track_size(client, &mut status, config, baseline);
+    }
+    if url.starts_with("https://")
+        && (config.tls_info || config.min_cert_days.is_some() || config.cert_expiry_info)
+    {
+        inspect_tls(&mut status, config);
+    }
+    if let Some(min) = config.min_response_time_ms
+        && (url.starts_with("http://") || url.starts_with("https://"))
+        && status.action_status.is_ok()
+        && status.response_time < Duration::from_millis(min)
+    {
+        status.too_fast = true;
+        if config.fail_too_fast && config.is_success(url, &status.action_status) {
+            status.action_status = Err(format!("suspiciously fast response, below the {}ms minimum", min));
+        }
+    }
+    status.success = config.is_success(url, &status.action_status);
+    if let Some(expr) = &config.success_expr {
+        match expr.evaluate(&status) {
+            // The expression replaces the status ranges, but a check that
+            // got no answer at all cannot pass
+            Ok(()) => status.success = status.action_status.is_ok(),
+            Err(failed) => {
+                status.success = false;
+                if status.action_status.is_ok() {
+                    status.action_status = Err(format!("success expression failed: {}", failed));
+                }
+            }
+        }
+    }
+    status.timestamp = SystemTime::now();
+
+    if config.abort_on_cert_error
+        && url.starts_with("https://")
+        && status.action_status.as_ref().is_err_and(|e| is_cert_error(e))
+    {
+        state.cert_failures.lock().unwrap().entry(host.clone()).or_insert_with(|| url.to_string());
+    }
+
+    if config.host_time_budget.is_some() {
+        *state.host_time.lock().unwrap().entry(host).or_default() += start.elapsed();
+    }
+
+    status
+}
+
+/// Whether a request failed because the server's certificate was rejected,
+/// as opposed to a refused connection or a protocol error
+fn is_cert_error(error: &str) -> bool {
+    error.contains("certificate verify failed") || error.contains("invalid peer certificate")
+}
+
+/// Record TLS version and certificate expiry from a separate handshake,
+/// failing the check when the certificate expires too soon
+fn inspect_tls(status: &mut WebsiteStatus, config: &Config) {
+    let details = tls::inspect(&status.url, config.timeout);
+
+    if config.tls_info {
+        status.tls_version = Some(details.as_ref().map(|d| d.version.clone()).map_err(Clone::clone));
+    }
+
+    if config.cert_expiry_info
+        && let Ok(details) = &details
+    {
+        status.cert_expires_in_days = details.cert_expires_in_days;
+        status.cert_not_after = details.cert_not_after.clone();
+    }
+
+    let Some(min_days) = config.min_cert_days else {
+        return;
+    };
+    match details {
+        Ok(details) => {
+            status.cert_expires_in_days = details.cert_expires_in_days;
+            status.cert_not_after = details.cert_not_after;
+            match details.cert_expires_in_days {
+                Some(days) if days < min_days && status.action_status.is_ok() => {
+                    status.action_status = Err(if days < 0 {
+                        format!("certificate expired {} days ago", -days)
+                    } else {
+                        format!("certificate expires in {} days (minimum {})", days, min_days)
+                    });
+                }
+                None if status.action_status.is_ok() => {
+                    status.action_status = Err("server sent no certificate".to_string());
+                }
+                _ => {}
+            }
+        }
+        Err(e) => {
+            if status.action_status.is_ok() {
+                status.action_status = Err(format!("certificate check failed: {}", e));
+            }
+        }
+    }
+}
+
+/// Run `check` up to `config.retries + 1` times, pausing briefly between
+/// attempts, and return the first success or the last error. With
+/// `config.retry_on_error` set only matching errors are retried, and with
+/// `config.retry_rate` every retry waits for the shared throttle. With
+/// `config.retry_on_http2_goaway` GOAWAY errors get retries of their own.
+/// Every retry is counted in `retries_used`
+fn with_retries<T>(
+    config: &Config,
+    retries_used: &mut u32,
+    mut check: impl FnMut() -> Result<T, String>,
+) -> Result<T, String> {
+    let mut attempt = 0;
+    let mut goaway_attempt = 0;
+    loop {
+        match check() {
+            // The server is going away in an orderly fashion, a new
+            // connection usually reaches an instance that stays
+            Err(e) if config.retry_on_http2_goaway
+                && goaway_attempt < MAX_GOAWAY_RETRIES
+                && e.starts_with(HTTP2_GOAWAY) =>
+            {
+                goaway_attempt += 1;
+                *retries_used += 1;
+                thread::sleep(Duration::from_millis(100));
+                if let Some(bucket) = &config.retry_bucket {
+                    bucket.acquire();
+                }
+            }
+            Err(e) if attempt < config.retries
+                && config.retry_on_error.as_ref().is_none_or(|pattern| pattern.is_match(&e)) =>
+            {
+                attempt += 1;
+                *retries_used += 1;
+                thread::sleep(Duration::from_millis(100));
+                if let Some(bucket) = &config.retry_bucket {
+                    bucket.acquire();
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Request a URL, retrying transport errors, and record the status code
+/// and response time of the first successful attempt
+fn check_http(client: &Client, status: &mut WebsiteStatus, config: &Config) {
+    let method = Method::from_bytes(config.method.as_bytes()).unwrap_or(Method::GET);
+
+    // Sending a POST or PUT again can repeat its side effects, such as a
+    // duplicate order, so only methods that never change server state are
+    // retried unless the user opts in
+    let safe = matches!(method, Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE);
+    let no_retries;
+    let config = if safe || config.retry_non_idempotent {
+        config
+    } else {
+        no_retries = Config { retries: 0, retry_on_http2_goaway: false, ..config.clone() };
+        &no_retries
+    };
+
+    // A URL that already fails is not worth downloading. Servers that do not
+    // implement HEAD get the GET anyway
+    if config.preflight_head && method == Method::GET && config.reads_body() {
+        let preflight = head_preflight(client, status, config);
+        let passed = preflight.success || matches!(preflight.action_status, Ok(405 | 501));
+        if !passed {
+            status.action_status = preflight.action_status.clone();
+            status.response_time = preflight.response_time;
+        }
+        status.head_preflight = Some(preflight);
+        if !passed {
+            return;
+        }
+    }
+
+    // An empty answer to HEAD is the whole point of HEAD
+    let min_body = if method == Method::HEAD {
+        None
+    } else {
+        config.min_response_bytes.or(config.check_response_not_empty.then_some(1))
+    };
+    let mut body_size = None;
+    let mut age_header = None;
+    let mut viewport_problem = None;
+
+    let result = with_retries(config, &mut status.retries_used, || {
+        let mut request = client.request(method.clone(), &status.url);
+        for (name, value) in &config.headers {
+            request = request.header(name, value);
+        }
+        if let Some(body) = config.url_bodies.get(&status.url) {
+            if !config.headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+                && serde_json::from_slice::<serde_json::Value>(body).is_ok()
+            {
+                request = request.header(CONTENT_TYPE, "application/json");
+            }
+            request = request.body(body.clone());
+        }
+        if !config.headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("accept")) {
+            // reqwest adds this itself when missing, set it here so the
+            // recorded request matches what goes over the wire
+            request = request.header("Accept", "*/*");
+        }
+        let request = request.build().map_err(|e| e.to_string())?;
+
+        if config.include_request_info {
+            status.request = Some(RequestInfo::from_request(&request, config.redact_headers));
+        }
+
+        let start = Instant::now();
+        status.requests_sent += 1;
+        let mut response = client.execute(request).map_err(|e| {
+            status.connections_closed += 1;
+            request_error(e)
+        })?;
+        let elapsed = start.elapsed();
+        if !keeps_alive(&response) {
+            status.connections_closed += 1;
+        }
+        if let Some(name) = &config.version_header {
+            status.version_header_value = response.headers()
+                .get(name)
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).trim().to_string());
+        }
+        status.content_encoding = response.headers()
+            .get(CONTENT_ENCODING)
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).trim().to_string())
+            .or_else(|| config.disable_compression.then(|| "identity".to_string()));
+        if config.max_content_age.is_some() {
+            age_header = content_age(response.headers()).map(|(age, header)| {
+                status.content_age_secs = Some(age);
+                header
+            });
+        }
+        if config.report_cookie_security || config.require_cookie_secure {
+            status.cookies = CookieSecurity::from_headers(response.headers());
+        }
+        if config.check_x_frame_options || config.require_x_frame_options {
+            status.framing_protection = framing_protection(response.headers());
+        }
+        if config.check_cache_headers || config.require_cacheable {
+            status.cache_policy = Some(CachePolicy::from_headers(response.headers()));
+        }
+        if config.check_expect_ct || config.require_expect_ct_enforce {
+            status.expect_ct = response.headers()
+                .get("expect-ct")
+                .map(|value| ExpectCt::parse(&String::from_utf8_lossy(value.as_bytes())));
+        }
+
+        let is_html = response.headers()
+            .get(CONTENT_TYPE)
+            .is_some_and(|value| String::from_utf8_lossy(value.as_bytes()).contains("html"));
+        let html_limit = if config.check_sri { MAX_SRI_HTML_BYTES } else { MAX_HTML_BYTES };
+        let mut body = Vec::new();
+        if config.save_bodies.is_some() || config.detect_soft_404 || config.schema.is_some() {
+            response.read_to_end(&mut body).map_err(|e| e.to_string())?;
+        } else if config.inspects_html() && is_html {
+            (&mut response).take(html_limit).read_to_end(&mut body).map_err(|e| e.to_string())?;
+        }
+        if config.detect_soft_404 {
+            status.body_fingerprint = Some(Fingerprint::of(&body));
+        }
+        // The body of an error usually says what went wrong
+        if config.capture_error_body && !config.is_success(&status.url, &Ok(response.status().as_u16())) {
+            if body.is_empty() {
+                (&mut response).take(config.max_body_size as u64).read_to_end(&mut body).map_err(|e| e.to_string())?;
+            }
+            let captured = &body[..body.len().min(config.max_body_size)];
+            status.error_body = Some(String::from_utf8_lossy(captured).into_owned());
+        }
+        // Error pages are not expected to match the API's schema
+        if let Some(schema) = &config.schema
+            && config.is_success(&status.url, &Ok(response.status().as_u16()))
+        {
+            status.schema_errors = schema.errors(&body);
+        }
+        if config.inspects_html() && is_html {
+            let html = &body[..body.len().min(html_limit as usize)];
+            let document = Html::parse_document(&String::from_utf8_lossy(html));
+            if config.min_link_count.is_some() || config.max_link_count.is_some() {
+                status.link_count = Some(html::link_count(&document));
+            }
+            if config.check_image_alt {
+                status.images_missing_alt = Some(html::images_missing_alt(&document));
+            }
+            if config.title_pattern.is_some() {
+                status.page_title = Some(html::title(&document).unwrap_or_default());
+            }
+            if config.check_open_graph {
+                let image = html::meta_property(&document, "og:image");
+                status.missing_og_tags = ["og:title", "og:description"]
+                    .into_iter()
+                    .filter(|tag| html::meta_property(&document, tag).is_none())
+                    .chain(image.is_none().then_some("og:image"))
+                    .map(String::from)
+                    .collect();
+                status.og_image = image.map(|image| response.url().join(&image).map(String::from).unwrap_or(image));
+            }
+            if config.check_viewport_meta {
+                status.viewport_meta = html::meta_name(&document, "viewport");
+                viewport_problem = html::viewport_problem(status.viewport_meta.as_deref(), config.require_viewport_initial_scale);
+                status.viewport_meta_ok = viewport_problem.is_none();
+            }
+            if config.check_structured_data {
+                (status.structured_data_types, status.structured_data_invalid) = html::structured_data(&document);
+            }
+            if config.check_sri {
+                status.missing_sri = html::missing_sri(&document, response.url());
+            }
+            if config.check_canonical
+                && let Some(href) = html::canonical(&document)
+            {
+                // Relative targets are resolved, so an equivalent spelling
+                // of the same URL is not a mismatch
+                let canonical = response.url().join(&href);
+                status.canonical_mismatch = canonical.as_ref().is_ok_and(|url| url != response.url());
+                status.canonical_url = Some(canonical.map(String::from).unwrap_or(href));
+            }
+        }
+        if let Some(dir) = &config.save_bodies {
+            let head = bodies::response_head(&response);
+            status.body_file = Some(
+                bodies::save(dir, &status.url, config.body_store_format, &head, &body)
+                    .map(|path| path.display().to_string())
+                    .map_err(|e| e.to_string()),
+            );
+        }
+
+        // Only as much of the body is read as the check needs
+        if let Some(needed) = min_body {
+            if (body.len() as u64) < needed {
+                (&mut response).take(needed - body.len() as u64).read_to_end(&mut body).map_err(|e| e.to_string())?;
+            }
+            status.body_empty = body.is_empty();
+            body_size = Some(body.len() as u64);
+        }
+
+        // A single byte tells an empty body apart without downloading it
+        if config.retry_on_empty_body
+            && body.is_empty()
+            && response.read(&mut [0u8; 1]).map_err(|e| e.to_string())? == 0
+        {
+            return Err("empty response body".to_string());
+        }
+
+        let total = if config.detailed_timing {
+            io::copy(&mut response, &mut io::sink()).map_err(|e| e.to_string())?;
+            Some(start.elapsed())
+        } else {
+            None
+        };
+        Ok((response.status().as_u16(), elapsed, total))
+    });
+
+    match result {
+        Ok((code, elapsed, total)) => {
+            status.action_status = Ok(code);
+            status.response_time = elapsed;
+            if config.detailed_timing {
+                status.timing = Some(TimingBreakdown {
+                    ttfb: Some(elapsed),
+                    total,
+                    ..TimingBreakdown::probe(&status.url, config.timeout)
+                });
+            }
+            // The requirements below only fail responses that would otherwise
+            // succeed, so an error status is reported as it is
+            if let (Some(size), Some(needed)) = (body_size, min_body)
+                && config.is_success(&status.url, &status.action_status)
+            {
+                if status.body_empty && config.check_response_not_empty {
+                    status.action_status = Err("empty response body".to_string());
+                } else if size < needed {
+                    status.action_status = Err(format!("response body has {} bytes, expected at least {}", size, needed));
+                }
+            }
+            check_version(status, config);
+            check_link_count(status, config);
+            check_title(status, config);
+            check_open_graph(status, config);
+            check_structured_data(status, config);
+            if !status.schema_errors.is_empty() && config.is_success(&status.url, &status.action_status) {
+                let noun = if status.schema_errors.len() == 1 { "violation" } else { "violations" };
+                status.action_status = Err(format!("JSON Schema {}: {}", noun, status.schema_errors.join("; ")));
+            }
+            if config.require_sri
+                && config.is_success(&status.url, &status.action_status)
+                && !status.missing_sri.is_empty()
+            {
+                status.action_status = Err(format!(
+                    "{} external resources without integrity: {}",
+                    status.missing_sri.len(),
+                    status.missing_sri.join(", ")
+                ));
+            }
+            if config.require_image_alt
+                && config.is_success(&status.url, &status.action_status)
+                && let Some(count) = status.images_missing_alt.filter(|count| *count > 0)
+            {
+                status.action_status = Err(format!("{} images without alt text", count));
+            }
+            if config.is_success(&status.url, &status.action_status)
+                && let Some(problem) = viewport_problem.take()
+            {
+                status.action_status = Err(problem);
+            }
+            if config.require_cacheable
+                && config.is_success(&status.url, &status.action_status)
+                && let Some(policy) = &status.cache_policy
+                && (policy.no_store || policy.private)
+            {
+                let directive = if policy.no_store { "no-store" } else { "private" };
+                status.action_status = Err(format!("response is not cacheable: Cache-Control {}", directive));
+            }
+            if let Some(max) = config.max_content_age
+                && config.is_success(&status.url, &status.action_status)
+            {
+                match (status.content_age_secs, age_header) {
+                    (Some(age), Some(header)) if age > max.as_secs() => {
+                        status.action_status = Err(format!("content is {}s old by its {} header, more than {}s", age, header, max.as_secs()));
+                    }
+                    (Some(_), _) => {}
+                    _ => status.action_status = Err("response has no Last-Modified or Date header".to_string()),
+                }
+            }
+            if config.require_cookie_secure
+                && config.is_success(&status.url, &status.action_status)
+            {
+                let insecure: Vec<&str> = status.cookies.iter()
+                    .filter(|cookie| !cookie.secure)
+                    .map(|cookie| cookie.name.as_str())
+                    .collect();
+                if !insecure.is_empty() {
+                    status.action_status = Err(format!("cookies without Secure: {}", insecure.join(", ")));
+                }
+            }
+            if config.require_x_frame_options
+                && config.is_success(&status.url, &status.action_status)
+            {
+                match &status.framing_protection {
+                    None => {
+                        status.action_status = Err("response has no X-Frame-Options or CSP frame-ancestors".to_string());
+                    }
+                    Some(value) if !value.starts_with("frame-ancestors")
+                        && !value.eq_ignore_ascii_case("DENY")
+                        && !value.eq_ignore_ascii_case("SAMEORIGIN") =>
+                    {
+                        status.action_status = Err(format!("X-Frame-Options {} does not prevent framing", value));
+                    }
+                    Some(_) => {}
+                }
+            }
+            if config.require_expect_ct_enforce
+                && config.is_success(&status.url, &status.action_status)
+            {
+                match &status.expect_ct {
+                    None => status.action_status = Err("response has no Expect-CT header".to_string()),
+                    Some(expect_ct) if !expect_ct.enforce => {
+                        status.action_status = Err("Expect-CT header is not in enforce mode".to_string());
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        Err(e) => status.action_status = Err(e),
+    }
+}
+
+/// Seconds since the content of a response changed, by its `Last-Modified`
+/// header or, without one, its `Date`, and which of the two it was. Dates
+/// in the future count as no age
+fn content_age(headers: &HeaderMap) -> Option<(u64, &'static str)> {
+    [(LAST_MODIFIED, "Last-Modified"), (DATE, "Date")]
+        .into_iter()
+        .find_map(|(name, label)| {
+            let value = headers.get(name)?.to_str().ok()?;
+            let date = DateTime::parse_from_rfc2822(value.trim()).ok()?;
+            Some(((Utc::now() - date.to_utc()).num_seconds().max(0) as u64, label))
+        })
+}
+
+/// The clickjacking protection of a response: the `frame-ancestors`
+/// directive of its `Content-Security-Policy`, which supersedes
+/// `X-Frame-Options`, or else the `X-Frame-Options` value as sent
+fn framing_protection(headers: &HeaderMap) -> Option<String> {
+    let frame_ancestors = headers.get_all(CONTENT_SECURITY_POLICY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|policy| policy.split(';'))
+        .map(str::trim)
+        .find(|directive| {
+            directive.split_whitespace()
+                .next()
+                .is_some_and(|name| name.eq_ignore_ascii_case("frame-ancestors"))
+        });
+    if let Some(directive) = frame_ancestors {
+        return Some(format!("frame-ancestors{}", &directive["frame-ancestors".len()..]));
+    }
+    headers.get(X_FRAME_OPTIONS)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).trim().to_string())
+}
+
+/// A failed request as text. Running out of file descriptors and HTTP/2
+/// GOAWAYs get recognizable messages instead of reqwest's generic "error
+/// sending request"
+fn request_error(error: reqwest::Error) -> String {
+    if limits::is_too_many_open_files(&error) {
+        limits::TOO_MANY_OPEN_FILES.to_string()
+    } else if is_http2_goaway(&error) {
+        format!("{}: {}", HTTP2_GOAWAY, error)
+    } else {
+        error.to_string()
+    }
+}
+
+/// Whether a request failed because the server sent an HTTP/2 GOAWAY or
+/// refused the stream, which servers do while they shut down gracefully.
+/// Both mean the server did not process the request
+fn is_http2_goaway(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut current = Some(error);
+    while let Some(error) = current {
+        if let Some(h2) = error.downcast_ref::<h2::Error>() {
+            return h2.is_go_away() || h2.reason() == Some(h2::Reason::REFUSED_STREAM);
+        }
+        current = error.source();
+    }
+    false
+}
+
+/// Fail the check when the version header does not satisfy the configured
+/// semver requirement. A leading `v` is accepted, as in `v2.5.0`
+fn check_version(status: &mut WebsiteStatus, config: &Config) {
+    let (Some(name), Some(requirement)) = (&config.version_header, &config.version_requirement) else {
+        return;
+    };
+
+    let failure = match &status.version_header_value {
+        None => Some(format!("response has no {} header", name)),
+        Some(value) => match Version::parse(value.trim_start_matches('v')) {
+            Ok(version) if requirement.matches(&version) => None,
+            Ok(version) => Some(format!("version {} does not satisfy {}", version, requirement)),
+            Err(e) => Some(format!("invalid version {:?} in {} header: {}", value, name, e)),
+        },
+    };
+    status.version_requirement_met = Some(failure.is_none());
+    if let Some(failure) = failure {
+        status.action_status = Err(failure);
+    }
+}
+
+/// Fail the check when an HTML page has fewer or more links than allowed.
+/// Pages that are not HTML, or fail for another reason, are left alone
+fn check_link_count(status: &mut WebsiteStatus, config: &Config) {
+    let (Some(count), Ok(_)) = (status.link_count, &status.action_status) else {
+        return;
+    };
+    if let Some(min) = config.min_link_count
+        && count < min
+    {
+        status.action_status = Err(format!("page has {} links, expected at least {}", count, min));
+    } else if let Some(max) = config.max_link_count
+        && count > max
+    {
+        status.action_status = Err(format!("page has {} links, expected at most {}", count, max));
+    }
+}
+
+/// Fail the check when the title of an HTML page does not match the
+/// configured pattern
+fn check_title(status: &mut WebsiteStatus, config: &Config) {
+    let (Some(pattern), Some(title), Ok(_)) = (&config.title_pattern, &status.page_title, &status.action_status) else {
+        return;
+    };
+    if title.is_empty() {
+        status.action_status = Err("page has no title".to_string());
+    } else if !pattern.is_match(title) {
+        status.action_status = Err(format!("page title {:?} does not match {}", title, pattern));
+    }
+}
+
+/// Fail the check when an HTML page lacks Open Graph tags, or has an
+/// `og:image` that is not https when that is required
+fn check_open_graph(status: &mut WebsiteStatus, config: &Config) {
+    if status.action_status.is_err() {
+        return;
+    }
+    if !status.missing_og_tags.is_empty() {
+        status.action_status = Err(format!("missing Open Graph tags: {}", status.missing_og_tags.join(", ")));
+    } else if config.require_og_image_https
+        && let Some(image) = &status.og_image
+        && !image.starts_with("https://")
+    {
+        status.action_status = Err(format!("og:image is not https: {}", image));
+    }
+}
+
+/// Fail the check when a required schema type is absent. Responses that are
+/// not HTML declare no types, so they fail too
+fn check_structured_data(status: &mut WebsiteStatus, config: &Config) {
+    if status.action_status.is_err() {
+        return;
+    }
+    let missing: Vec<&str> = config.required_structured_data_types.iter()
+        .filter(|required| !status.structured_data_types.contains(required))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        let noun = if missing.len() == 1 { "type" } else { "types" };
+        status.action_status = Err(format!("structured data {} {} not found", noun, missing.join(", ")));
+    }
+}
+
+/// Ask for the size of a URL with a HEAD request and compare it with the
+/// baseline. The header is read directly: reqwest reports the (empty) body
+/// length for HEAD responses
+fn track_size(client: &Client, status: &mut WebsiteStatus, config: &Config, baseline: &HashMap<String, u64>) {
+    let mut request = client.head(&status.url);
+    for (name, value) in &config.headers {
+        request = request.header(name, value);
+    }
+    status.content_length = request.send()
+        .ok()
+        .and_then(|response| {
+            response.headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok())
+        });
+
+    let (Some(length), Some(&previous)) = (status.content_length, baseline.get(&status.url)) else {
+        return;
+    };
+    let delta = length as i64 - previous as i64;
+    status.size_delta = Some(delta);
+    status.size_changed = if previous == 0 {
+        length != 0
+    } else {
+        delta.unsigned_abs() as f64 / previous as f64 * 100.0 > config.size_change_threshold
+    };
+}
+
+/// Send the HEAD of a HEAD preflight, with retries, counting its requests
+/// in `status`
+fn head_preflight(client: &Client, status: &mut WebsiteStatus, config: &Config) -> ProbeResult {
+    let mut response_time = Duration::ZERO;
+    let action_status = with_retries(config, &mut status.retries_used, || {
+        let mut request = client.head(&status.url);
+        for (name, value) in &config.headers {
+            request = request.header(name, value);
+        }
+        let start = Instant::now();
+        status.requests_sent += 1;
+        let response = request.send().map_err(|e| {
+            status.connections_closed += 1;
+            request_error(e)
+        })?;
+        response_time = start.elapsed();
+        if !keeps_alive(&response) {
+            status.connections_closed += 1;
+        }
+        Ok(response.status().as_u16())
+    });
+    ProbeResult {
+        path: Url::parse(&status.url).map(|url| url.path().to_string()).unwrap_or_default(),
+        success: config.is_success(&status.url, &action_status),
+        action_status,
+        response_time,
+    }
+}
+
+/// Whether the server left the connection open for another request
+fn keeps_alive(response: &Response) -> bool {
+    let connection = response.headers()
+        .get(CONNECTION)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    if response.version() == HttpVersion::HTTP_10 {
+        connection.contains("keep-alive")
+    } else {
+        !connection.contains("close")
+    }
+}
+
+/// Whether an HTTP URL names just a host, with no path or query
+fn is_host_only(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|u| u.path() == "/" && u.query().is_none())
+}
+
+/// Request the URL again with its trailing slash added or removed and
+/// record whether the status differs. Host-only URLs are skipped, their
+/// path is always `/`
+fn check_trailing_slash(client: &Client, status: &mut WebsiteStatus, config: &Config) {
+    let Ok(mut url) = Url::parse(&status.url) else {
+        return;
+    };
+    if url.path() == "/" {
+        return;
+    }
+    let path = match url.path().strip_suffix('/') {
+        Some(trimmed) => trimmed.to_string(),
+        None => format!("{}/", url.path()),
+    };
+    url.set_path(&path);
+
+    let mut variant = WebsiteStatus::new(url.as_str());
+    check_http(client, &mut variant, config);
+    status.requests_sent += variant.requests_sent;
+    status.connections_closed += variant.connections_closed;
+    status.retries_used += variant.retries_used;
+    status.trailing_slash_mismatch = variant.action_status.as_ref().ok() != status.action_status.as_ref().ok();
+    status.trailing_slash_variant = Some(ProbeResult {
+        path,
+        success: config.is_success(url.as_str(), &variant.action_status),
+        action_status: variant.action_status,
+        response_time: variant.response_time,
+    });
+}
+
+/// Check every probe path on a host and combine them into one verdict:
+/// healthy when all paths pass, or any with `config.probe_any`. The
+/// response time is the total over all paths
+fn check_probes(client: &Client, status: &mut WebsiteStatus, config: &Config) {
+    let base = status.url.trim_end_matches('/').to_string();
+    let mut probes = Vec::with_capacity(config.probe_paths.len());
+    for path in &config.probe_paths {
+        let url = format!("{}/{}", base, path.trim_start_matches('/'));
+        let mut probe = WebsiteStatus::new(&url);
+        check_http(client, &mut probe, config);
+
+        status.response_time += probe.response_time;
+        status.requests_sent += probe.requests_sent;
+        status.connections_closed += probe.connections_closed;
+        status.retries_used += probe.retries_used;
+        probes.push(ProbeResult {
+            path: path.clone(),
+            success: config.is_success(&url, &probe.action_status),
+            action_status: probe.action_status,
+            response_time: probe.response_time,
+        });
+    }
+
+    let describe = |probe: &ProbeResult| match &probe.action_status {
+        Ok(code) => format!("probe {} returned HTTP {}", probe.path, code),
+        Err(e) => format!("probe {} failed: {}", probe.path, e),
+    };
+    status.action_status = if config.probe_any {
+        match probes.iter().find(|p| p.success) {
+            Some(passed) => passed.action_status.clone(),
+            None => Err(format!(
+                "no probe path passed: {}",
+                probes.iter().map(describe).collect::<Vec<_>>().join("; ")
+            )),
+        }
+    } else {
+        match probes.iter().find(|p| !p.success) {
+            Some(failed) => Err(describe(failed)),
+            None => probes.last().map_or(Err("no probe paths".to_string()), |p| p.action_status.clone()),
+        }
+    };
+    status.probes = Some(probes);
+}
+
+/// Request a URL `config.request_count` times in a row and summarize the
+/// response times of the successful samples. The reported status is the
+/// last sample's, the response time the mean
+fn check_http_samples(client: &Client, status: &mut WebsiteStatus, config: &Config) {
+    let mut times = Vec::with_capacity(config.request_count as usize);
+    for _ in 0..config.request_count {
+        check_http(client, status, config);
+        if status.action_status.is_ok() {
+            times.push(status.response_time);
+        }
+    }
+    status.sample_count = config.request_count;
+
+    if times.is_empty() {
+        return;
+    }
+    times.sort();
+    let mean = times.iter().sum::<Duration>() / times.len() as u32;
+    status.response_time = mean;
+    status.latency = Some(LatencyStats {
+        min: times[0],
+        max: times[times.len() - 1],
+        mean,
+        p95: percentile(&times, 95.0),
+    });
+}
+
+/// Ping a host, retrying lost packets. A reply is reported as status 0,
+/// the ICMP echo reply type
+fn check_icmp(status: &mut WebsiteStatus, config: &Config) {
+    let host = status.url.trim_start_matches("icmp://");
+
+    match with_retries(config, &mut status.retries_used, || icmp::ping(host, config.timeout)) {
+        Ok(rtt) => {
+            status.action_status = Ok(0);
+            status.response_time = rtt;
+        }
+        Err(e) => status.action_status = Err(e),
+    }
+}
+
+/// Exchange greetings with an SMTP server. The response time covers the
+/// whole exchange, the greeting code is reported as the status
+fn check_smtp(status: &mut WebsiteStatus, config: &Config) {
+    let addr = status.url.trim_start_matches("smtp://").to_string();
+
+    let result = with_retries(config, &mut status.retries_used, || {
+        let start = Instant::now();
+        let banner = smtp::check(&addr, config.smtp_ehlo, config.timeout)?;
+        Ok((banner, start.elapsed()))
+    });
+
+    match result {
+        Ok((banner, elapsed)) => {
+            status.action_status = Ok(220);
+            status.response_time = elapsed;
+            status.smtp_banner = Some(banner);
+        }
+        Err(e) => status.action_status = Err(e),
+    }
+}
+
+/// Open a WebSocket connection, timing the handshake. Success is reported
+/// as the `101 Switching Protocols` status of the handshake response
+fn check_websocket(status: &mut WebsiteStatus, config: &Config) {
+    let result = with_retries(config, &mut status.retries_used, || {
+        let start = Instant::now();
+        let code = websocket::handshake(&status.url, &config.headers, config.timeout)?;
+        Ok((code, start.elapsed()))
+    });
+
+    match result {
+        Ok((code, elapsed)) => {
+            status.action_status = Ok(code);
+            status.response_time = elapsed;
+        }
+        Err(e) => status.action_status = Err(e),
+    }
+}
+
+/// Read an SSH server's version string and, if configured, require a
+/// minimum OpenSSH release. The banner is reported as status 0
+fn check_ssh(status: &mut WebsiteStatus, config: &Config) {
+    let addr = status.url.trim_start_matches("ssh://").to_string();
+
+    let result = with_retries(config, &mut status.retries_used, || {
+        let start = Instant::now();
+        let banner = ssh::check(&addr, config.timeout)?;
+        Ok((banner, start.elapsed()))
+    });
+
+    let (banner, elapsed) = match result {
+        Ok(received) => received,
+        Err(e) => {
+            status.action_status = Err(e);
+            return;
+        }
+    };
+    status.response_time = elapsed;
+
+    status.action_status = match (config.min_ssh_version, ssh::openssh_version(&banner)) {
+        (None, _) => Ok(0),
+        (Some(_), None) => Err("not an OpenSSH server".to_string()),
+        (Some(min), Some(found)) if found < min => Err(format!(
+            "OpenSSH {}.{} is older than required {}.{}",
+            found.0, found.1, min.0, min.1
+        )),
+        (Some(_), Some(_)) => Ok(0),
+    };
+    status.ssh_version = Some(banner);
+}
+
+/// Ask every configured resolver for the A and AAAA records of a host and
+/// flag the check when their answers differ
+fn check_dns(status: &mut WebsiteStatus, config: &Config) {
+    let host = status.url.trim_start_matches("dns://").to_string();
+    let start = Instant::now();
+
+    let mut dns_results = HashMap::new();
+    let mut failures = Vec::new();
+    for resolver in &config.dns_resolvers {
+        let name = dns::resolver_name(resolver);
+        match with_retries(config, &mut status.retries_used, || dns::lookup(*resolver, &host, config.timeout)) {
+            Ok(addresses) => {
+                dns_results.insert(name, addresses);
+            }
+            Err(e) => {
+                failures.push(format!("{}: {}", name, e));
+                dns_results.insert(name, Vec::new());
+            }
+        }
+    }
+    status.response_time = start.elapsed();
+
+    let mut answers = dns_results.values();
+    let first = answers.next();
+    status.dns_inconsistent = !failures.is_empty() || answers.any(|a| Some(a) != first);
+    status.action_status = if !failures.is_empty() {
+        Err(format!("resolver errors: {}", failures.join("; ")))
+    } else if status.dns_inconsistent {
+        Err("resolvers returned different answers".to_string())
+    } else {
+        Ok(0)
+    };
+    status.dns_results = Some(dns_results);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, fmt};
+
+    use super::*;
+
+    /// An error that hides its cause behind `source()`, as reqwest does
+    #[derive(Debug)]
+    struct Wrapped(h2::Error);
+
+    impl fmt::Display for Wrapped {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("error sending request")
+        }
+    }
+
+    impl Error for Wrapped {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    /// The error of a request sent to a server that answers with GOAWAY
+    /// before it processes any stream
+    fn go_away_error() -> h2::Error {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            let (client_io, server_io) = tokio::io::duplex(65536);
+            let server = tokio::spawn(async move {
+                let mut connection = h2::server::handshake(server_io).await.unwrap();
+                connection.abrupt_shutdown(h2::Reason::NO_ERROR);
+                while let Some(Ok(_)) = connection.accept().await {}
+            });
+            let (mut client, connection) = h2::client::handshake(client_io).await.unwrap();
+            tokio::spawn(connection);
+            let request = http::Request::get("http://localhost/").body(()).unwrap();
+            let (response, _) = client.send_request(request, true).unwrap();
+            let error = response.await.unwrap_err();
+            server.await.unwrap();
+            error
+        })
+    }
+
+    #[test]
+    fn recognizes_go_away_behind_sources() {
+        let error = go_away_error();
+        assert!(error.is_go_away());
+        assert!(is_http2_goaway(&Wrapped(error)));
+    }
+
+    #[test]
+    fn recognizes_refused_stream() {
+        assert!(is_http2_goaway(&Wrapped(h2::Reason::REFUSED_STREAM.into())));
+    }
+
+    #[test]
+    fn ignores_other_stream_errors() {
+        assert!(!is_http2_goaway(&Wrapped(h2::Reason::PROTOCOL_ERROR.into())));
+    }
+}
@@ -0,0 +1,529 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use regex::Regex;
+use semver::VersionReq;
+use serde::{Deserialize, Serialize};
+
+use crate::{status::is_sensitive_header, throttle::TokenBucket, JsonSchema, SuccessExpr};
+
+/// Settings shared by every worker during a scan.
+///
+/// Serialized as TOML for config files, durations as whole seconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Number of concurrent worker threads
+    pub workers: usize,
+    /// Let the pool grow from `workers` up to this many threads while URLs
+    /// wait in the queue, instead of staying fixed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_workers: Option<usize>,
+    /// Seed of every random value, random per run when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Cap on concurrent checks against one resolved IP address, shared by
+    /// every hostname that resolves to it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_ip_limit: Option<usize>,
+    /// Grow concurrency from one check to `workers` over this long
+    #[serde(with = "optional_secs", skip_serializing_if = "Option::is_none")]
+    pub ramp_up: Option<Duration>,
+    /// Report results in input order instead of completion order
+    pub ordered_output: bool,
+    /// Local address HTTP requests are sent from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_ip: Option<IpAddr>,
+    /// Timeout for each request
+    #[serde(with = "secs")]
+    pub timeout: Duration,
+    /// Request timeout in seconds for a domain and its subdomains, instead
+    /// of `timeout`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub domain_timeouts: BTreeMap<String, u64>,
+    /// Retry attempts for failed requests
+    pub retries: u32,
+    /// Retry HTTP methods other than GET, HEAD, OPTIONS and TRACE
+    pub retry_non_idempotent: bool,
+    /// Only retry errors whose message matches this, every error when unset
+    #[serde(with = "pattern", skip_serializing_if = "Option::is_none")]
+    pub retry_on_error: Option<Arc<Regex>>,
+    /// Retry requests cut off by an HTTP/2 GOAWAY or refused stream, as
+    /// during a graceful server restart, on top of `retries`
+    pub retry_on_http2_goaway: bool,
+    /// Retry attempts started per second across all workers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_rate: Option<f64>,
+    /// Throttle built from `retry_rate` at the start of a scan
+    #[serde(skip)]
+    pub(crate) retry_bucket: Option<Arc<TokenBucket>>,
+    /// Record the negotiated TLS version of https URLs
+    pub tls_info: bool,
+    /// Inclusive status code ranges counted as success, 2xx when empty
+    pub success_ranges: Vec<(u16, u16)>,
+    /// Decides success in place of the status ranges when set
+    #[serde(with = "expression", skip_serializing_if = "Option::is_none")]
+    pub success_expr: Option<Arc<SuccessExpr>>,
+    /// JSON Schema response bodies must match
+    #[serde(with = "schema_file", skip_serializing_if = "Option::is_none")]
+    pub schema: Option<Arc<JsonSchema>>,
+    /// Send `EHLO` during SMTP checks and require a `250` reply
+    pub smtp_ehlo: bool,
+    /// Cap on the cumulative time spent on one host, later URLs for the
+    /// host are skipped once it is used up
+    #[serde(with = "optional_secs", skip_serializing_if = "Option::is_none")]
+    pub host_time_budget: Option<Duration>,
+    /// Minimum OpenSSH release, as `(major, minor)`, SSH servers must run
+    pub min_ssh_version: Option<(u32, u32)>,
+    /// HTTP request method
+    pub method: String,
+    /// Request body of each URL that has one, from the URL files
+    #[serde(skip)]
+    pub url_bodies: Arc<HashMap<String, Vec<u8>>>,
+    /// Retry count of each URL annotated with one in the URL files, used
+    /// instead of `retries`
+    #[serde(skip)]
+    pub url_retries: Arc<HashMap<String, u32>>,
+    /// Extra headers sent with every HTTP request
+    pub headers: Vec<(String, String)>,
+    /// Record method, headers and body length of each HTTP request
+    pub include_request_info: bool,
+    /// Mask `Authorization` and `Cookie` values in recorded requests
+    pub redact_headers: bool,
+    /// Resolvers compared by DNS propagation checks
+    pub dns_resolvers: Vec<SocketAddr>,
+    /// Name servers that resolve the hosts of HTTP checks instead of the
+    /// system resolver, tried in order
+    pub dns_servers: Vec<SocketAddr>,
+    /// Record diagnostic details, such as the DNS server that answered
+    pub verbose: bool,
+    /// Record a per-phase timing breakdown for HTTP checks
+    pub detailed_timing: bool,
+    /// Fail https checks whose certificate expires in fewer days than this
+    pub min_cert_days: Option<i64>,
+    /// Record when the certificate of each https URL expires
+    pub cert_expiry_info: bool,
+    /// Treat an empty response body like a transport error and retry
+    pub retry_on_empty_body: bool,
+    /// Fail HTTP responses, other than to HEAD, whose body is empty
+    pub check_response_not_empty: bool,
+    /// Ask servers for uncompressed responses by sending no
+    /// `Accept-Encoding`, not even one given in `headers`
+    pub disable_compression: bool,
+    /// Fail HTTP responses, other than to HEAD, whose body is shorter
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_response_bytes: Option<u64>,
+    /// Send a HEAD first and only download the body with a GET when it
+    /// succeeds
+    pub preflight_head: bool,
+    /// Requests sent to each HTTP URL, one after another
+    pub request_count: u32,
+    /// Only check hosts matching one of these, any host when empty
+    pub host_allowlist: Vec<String>,
+    /// Never check hosts matching one of these, takes precedence over the allowlist
+    pub host_denylist: Vec<String>,
+    /// Response header carrying the deployed software version
+    pub version_header: Option<String>,
+    /// Semver requirement the version header must satisfy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_requirement: Option<VersionReq>,
+    /// Content lengths from a previous run. When set, every HTTP URL also
+    /// gets a HEAD request whose `Content-Length` is compared against it
+    #[serde(skip)]
+    pub size_baseline: Option<Arc<HashMap<String, u64>>>,
+    /// Size change, in percent of the baseline, above which a URL is flagged
+    pub size_change_threshold: f64,
+    /// HTTP responses faster than this many milliseconds are flagged as
+    /// suspicious, such as a cached error page or a WAF's canned answer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_response_time_ms: Option<u64>,
+    /// Fail flagged responses instead of only reporting them
+    pub fail_too_fast: bool,
+    /// Paths checked on every host-only HTTP URL instead of the URL itself
+    pub probe_paths: Vec<String>,
+    /// Probed hosts are healthy when any path passes rather than all
+    pub probe_any: bool,
+    /// Also request every HTTP URL with its trailing slash toggled and flag
+    /// URLs where the two answers differ
+    pub check_trailing_slash: bool,
+    /// Skip the remaining https URLs of a host once one failed certificate
+    /// verification, reporting them with a shared reason
+    pub abort_on_cert_error: bool,
+    /// Record the `Expect-CT` header of HTTP responses
+    pub check_expect_ct: bool,
+    /// Fail HTTP checks whose `Expect-CT` header is missing or lacks `enforce`
+    pub require_expect_ct_enforce: bool,
+    /// Record the clickjacking protection of HTTP responses
+    pub check_x_frame_options: bool,
+    /// Fail HTTP checks without `X-Frame-Options: DENY` or `SAMEORIGIN`, or
+    /// a CSP `frame-ancestors`
+    pub require_x_frame_options: bool,
+    /// Record the caching headers of HTTP responses
+    pub check_cache_headers: bool,
+    /// Fail HTTP checks whose response is `no-store` or `private`
+    pub require_cacheable: bool,
+    /// Fail HTTP responses whose `Last-Modified`, or `Date` without it, is
+    /// older than this
+    #[serde(with = "optional_secs", skip_serializing_if = "Option::is_none")]
+    pub max_content_age: Option<Duration>,
+    /// Record the security attributes of cookies set by HTTP responses
+    pub report_cookie_security: bool,
+    /// Fail HTTP checks that set a cookie without `Secure`
+    pub require_cookie_secure: bool,
+    /// Fail HTML pages with fewer `<a href>` links than this
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_link_count: Option<u32>,
+    /// Fail HTML pages with more `<a href>` links than this
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_link_count: Option<u32>,
+    /// Fail HTML pages whose `<title>` does not match this
+    #[serde(with = "pattern", skip_serializing_if = "Option::is_none")]
+    pub title_pattern: Option<Arc<Regex>>,
+    /// Record the canonical URL of HTML pages and flag pages where it is not
+    /// the URL that was finally fetched
+    pub check_canonical: bool,
+    /// Compare 2xx pages against the answer to a path that should not exist
+    /// on their host and flag the ones that look the same
+    pub detect_soft_404: bool,
+    /// Fail HTML pages missing an `og:title`, `og:description` or `og:image`
+    pub check_open_graph: bool,
+    /// Also fail pages whose `og:image` is not an https URL
+    pub require_og_image_https: bool,
+    /// Fail HTML pages without a `width=device-width` viewport meta tag
+    pub check_viewport_meta: bool,
+    /// Also require `initial-scale=1` in the viewport meta tag
+    pub require_viewport_initial_scale: bool,
+    /// Count the images of HTML pages that have no alt text
+    pub check_image_alt: bool,
+    /// Fail HTML pages with any image that has no alt text
+    pub require_image_alt: bool,
+    /// Record the cross-origin scripts and stylesheets of HTML pages that
+    /// have no `integrity` attribute
+    pub check_sri: bool,
+    /// Fail HTML pages with any such resource
+    pub require_sri: bool,
+    /// Record the schema types declared by JSON-LD and microdata on HTML pages
+    pub check_structured_data: bool,
+    /// Fail HTML pages that do not declare each of these types
+    pub required_structured_data_types: Vec<String>,
+    /// Directory the body of every HTTP response is saved in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub save_bodies: Option<PathBuf>,
+    pub body_store_format: BodyStoreFormat,
+    /// Record the body of responses whose status is not a success
+    pub capture_error_body: bool,
+    /// Bytes of a captured error body kept
+    pub max_body_size: usize,
+}
+
+/// How saved response bodies are stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BodyStoreFormat {
+    /// The body exactly as received
+    #[default]
+    Raw,
+    /// Status line and response headers, a blank line, then the body
+    WithHeaders,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            workers: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            max_workers: None,
+            seed: None,
+            per_ip_limit: None,
+            ramp_up: None,
+            ordered_output: false,
+            source_ip: None,
+            timeout: Duration::from_secs(5),
+            domain_timeouts: BTreeMap::new(),
+            retries: 0,
+            retry_non_idempotent: false,
+            retry_on_error: None,
+            retry_on_http2_goaway: false,
+            retry_rate: None,
+            retry_bucket: None,
+            tls_info: false,
+            success_ranges: Vec::new(),
+            success_expr: None,
+            schema: None,
+            smtp_ehlo: false,
+            host_time_budget: None,
+            min_ssh_version: None,
+            method: "GET".to_string(),
+            url_bodies: Arc::default(),
+            url_retries: Arc::default(),
+            headers: Vec::new(),
+            include_request_info: false,
+            redact_headers: true,
+            dns_resolvers: [[8, 8, 8, 8], [1, 1, 1, 1], [9, 9, 9, 9]]
+                .into_iter()
+                .map(|ip| SocketAddr::new(IpAddr::V4(Ipv4Addr::from(ip)), 53))
+                .collect(),
+            dns_servers: Vec::new(),
+            verbose: false,
+            detailed_timing: false,
+            min_cert_days: None,
+            cert_expiry_info: false,
+            retry_on_empty_body: false,
+            check_response_not_empty: false,
+            disable_compression: false,
+            min_response_bytes: None,
+            preflight_head: false,
+            request_count: 1,
+            host_allowlist: Vec::new(),
+            host_denylist: Vec::new(),
+            version_header: None,
+            version_requirement: None,
+            size_baseline: None,
+            size_change_threshold: 10.0,
+            min_response_time_ms: None,
+            fail_too_fast: false,
+            probe_paths: Vec::new(),
+            probe_any: false,
+            check_trailing_slash: false,
+            abort_on_cert_error: false,
+            check_expect_ct: false,
+            require_expect_ct_enforce: false,
+            check_x_frame_options: false,
+            require_x_frame_options: false,
+            check_cache_headers: false,
+            require_cacheable: false,
+            max_content_age: None,
+            report_cookie_security: false,
+            require_cookie_secure: false,
+            min_link_count: None,
+            max_link_count: None,
+            title_pattern: None,
+            check_canonical: false,
+            detect_soft_404: false,
+            check_open_graph: false,
+            require_og_image_https: false,
+            check_viewport_meta: false,
+            require_viewport_initial_scale: false,
+            check_image_alt: false,
+            require_image_alt: false,
+            check_sri: false,
+            require_sri: false,
+            check_structured_data: false,
+            required_structured_data_types: Vec::new(),
+            save_bodies: None,
+            body_store_format: BodyStoreFormat::Raw,
+            capture_error_body: false,
+            max_body_size: 4096,
+        }
+    }
+}
+
+/// Durations as whole seconds
+mod secs {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_secs())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_secs)
+    }
+}
+
+/// Optional durations as whole seconds
+mod optional_secs {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => serializer.serialize_some(&duration.as_secs()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        Ok(Option::<u64>::deserialize(deserializer)?.map(Duration::from_secs))
+    }
+}
+
+/// Regular expressions as their source text
+mod pattern {
+    use std::sync::Arc;
+
+    use regex::Regex;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(regex: &Option<Arc<Regex>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match regex {
+            Some(regex) => serializer.serialize_some(regex.as_str()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Arc<Regex>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|source| Regex::new(&source).map(Arc::new).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+/// Success expressions as their source text
+mod expression {
+    use std::sync::Arc;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use crate::SuccessExpr;
+
+    pub fn serialize<S: Serializer>(expr: &Option<Arc<SuccessExpr>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match expr {
+            Some(expr) => serializer.serialize_some(expr.as_str()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Arc<SuccessExpr>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|source| SuccessExpr::parse(&source).map(Arc::new).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+/// JSON Schemas as the path of their file
+mod schema_file {
+    use std::sync::Arc;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use crate::JsonSchema;
+
+    pub fn serialize<S: Serializer>(schema: &Option<Arc<JsonSchema>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match schema {
+            Some(schema) => serializer.serialize_some(schema.path()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Arc<JsonSchema>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|path| JsonSchema::load(&path).map(Arc::new).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+impl Config {
+    /// The `domain_timeouts` entry covering `host`, the most specific one
+    /// when several do
+    pub(crate) fn domain_timeout(&self, host: &str) -> Option<&str> {
+        let host = host.to_ascii_lowercase();
+        self.domain_timeouts.keys()
+            .filter(|domain| host == **domain || host.strip_suffix(domain.as_str()).is_some_and(|sub| sub.ends_with('.')))
+            .max_by_key(|domain| domain.len())
+            .map(String::as_str)
+    }
+
+    /// Whether HTTP checks download the response body
+    pub(crate) fn reads_body(&self) -> bool {
+        self.save_bodies.is_some() || self.detect_soft_404 || self.schema.is_some() || self.inspects_html()
+    }
+
+    /// Whether HTTP checks download and parse the page as HTML
+    pub(crate) fn inspects_html(&self) -> bool {
+        self.min_link_count.is_some() || self.max_link_count.is_some() || self.title_pattern.is_some() || self.check_canonical || self.check_open_graph || self.check_structured_data
+            || self.check_viewport_meta
+            || self.check_image_alt
+            || self.check_sri
+    }
+
+    /// Read a config file written by [`Config::to_toml`] or by hand, with
+    /// missing settings left at their defaults
+    pub fn from_toml(input: &str) -> Result<Config, String> {
+        toml::from_str(input).map_err(|e| e.to_string())
+    }
+
+    /// Render the config as TOML that [`Config::from_toml`] reads back unchanged
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|e| e.to_string())
+    }
+
+    /// A copy safe to print: values of credential headers such as
+    /// `Authorization` are replaced with `[REDACTED]`
+    pub fn redacted(&self) -> Config {
+        let headers = self.headers.iter()
+            .map(|(name, value)| {
+                if is_sensitive_header(name) {
+                    (name.clone(), "[REDACTED]".to_string())
+                } else {
+                    (name.clone(), value.clone())
+                }
+            })
+            .collect();
+        Config { headers, ..self.clone() }
+    }
+
+    /// Whether a check outcome counts as healthy. Non-HTTP checks succeed
+    /// whenever they complete without error
+    pub fn is_success(&self, url: &str, action_status: &Result<u16, String>) -> bool {
+        let code = match action_status {
+            Ok(code) => *code,
+            Err(_) => return false,
+        };
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return true;
+        }
+
+        if self.success_ranges.is_empty() {
+            (200..300).contains(&code)
+        } else {
+            self.success_ranges.iter().any(|(low, high)| (*low..=*high).contains(&code))
+        }
+    }
+
+    /// Why `host` is out of scope under the allow and deny lists, if it is
+    pub fn host_rejection(&self, host: &str) -> Option<String> {
+        if self.host_denylist.iter().any(|pattern| host_matches(host, pattern)) {
+            return Some(format!("host {} is denylisted", host));
+        }
+        if !self.host_allowlist.is_empty()
+            && !self.host_allowlist.iter().any(|pattern| host_matches(host, pattern))
+        {
+            return Some(format!("host {} is not allowlisted", host));
+        }
+        None
+    }
+}
+
+/// Whether `host` is `pattern` or one of its subdomains, ignoring case
+fn host_matches(host: &str, pattern: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    let pattern = pattern.trim_start_matches("*.").trim_end_matches('.').to_ascii_lowercase();
+    host == pattern || host.ends_with(&format!(".{}", pattern))
+}
+
+/// Parse a comma separated list of status ranges such as `200-299,304`
+pub fn parse_status_ranges(input: &str) -> Result<Vec<(u16, u16)>, String> {
+    input
+        .split(',')
+        .map(|part| {
+            let part = part.trim();
+            let (low, high) = part.split_once('-').unwrap_or((part, part));
+            let low = low.trim().parse::<u16>();
+            let high = high.trim().parse::<u16>();
+            match (low, high) {
+                (Ok(low), Ok(high)) if low <= high => Ok((low, high)),
+                _ => Err(format!("Invalid status range: {}", part)),
+            }
+        })
+        .collect()
+}
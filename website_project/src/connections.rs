@@ -0,0 +1,66 @@
+use crate::{checker::target_host, status::escape_json, WebsiteStatus};
+
+/// Estimated connection reuse for one host.
+///
+/// reqwest does not expose its pool, so connections are counted from the
+/// responses: the first request opens one, and every request after a
+/// response that closed the connection (or a transport error) opens
+/// another. Workers hitting the same host in parallel open more than this,
+/// so the connection count is a lower bound.
+#[derive(Debug, Clone)]
+pub struct ConnectionStats {
+    pub host: String,
+    pub requests: u32,
+    pub connections: u32,
+}
+
+impl ConnectionStats {
+    /// Share of requests that went over an already open connection
+    pub fn reused_percent(&self) -> f64 {
+        if self.requests == 0 {
+            return 0.0;
+        }
+        f64::from(self.requests - self.connections) / f64::from(self.requests) * 100.0
+    }
+}
+
+/// Per-host connection reuse over the HTTP checks of a scan, sorted by host
+pub fn from_results(results: &[WebsiteStatus]) -> Vec<ConnectionStats> {
+    // (requests, responses that closed the connection) per host
+    let mut hosts: Vec<(String, u32, u32)> = Vec::new();
+    for result in results.iter().filter(|r| r.requests_sent > 0) {
+        let host = target_host(&result.url);
+        match hosts.iter_mut().find(|(h, _, _)| *h == host) {
+            Some((_, requests, closed)) => {
+                *requests += result.requests_sent;
+                *closed += result.connections_closed;
+            }
+            None => hosts.push((host, result.requests_sent, result.connections_closed)),
+        }
+    }
+
+    hosts.sort();
+    hosts.into_iter()
+        .map(|(host, requests, closed)| ConnectionStats {
+            host,
+            requests,
+            connections: (closed + 1).min(requests),
+        })
+        .collect()
+}
+
+pub fn to_json_string(stats: &[ConnectionStats]) -> String {
+    format!(
+        "[{}]",
+        stats.iter()
+            .map(|s| format!(
+                "{{\"host\": \"{}\", \"requests\": {}, \"connections\": {}, \"reused_percent\": {:.1}}}",
+                escape_json(&s.host),
+                s.requests,
+                s.connections,
+                s.reused_percent()
+            ))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
@@ -0,0 +1,120 @@
+//! Process control for long-running interval mode: a PID file and signal
+//! handlers a supervisor can use to poke the checker.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use signal_hook::{
+    consts::{SIGHUP, SIGINT, SIGTERM, SIGUSR1},
+    iterator::Signals,
+};
+
+use crate::{output, TimeUnit, TimestampFormat, WebsiteStatus};
+
+/// Where `SIGUSR1` dumps the latest result of every URL
+pub const DUMP_PATH: &str = "status.dump.json";
+
+/// Signal state shared between the main loop and the signal thread.
+///
+/// `SIGHUP` cuts the wait before the next round short and has the caller
+/// reload its URL list, `SIGUSR1` dumps the
+/// latest results to [`DUMP_PATH`], `SIGINT` and `SIGTERM` remove the PID
+/// file and exit.
+pub struct Supervisor {
+    pid_file: Option<PathBuf>,
+    timestamps: TimestampFormat,
+    time_unit: TimeUnit,
+    recheck: AtomicBool,
+    /// Most recent result per URL, in the order URLs were first seen
+    latest: Mutex<(Vec<String>, HashMap<String, WebsiteStatus>)>,
+}
+
+impl Supervisor {
+    /// Write the PID file, if any, and start handling signals
+    pub fn start(pid_file: Option<PathBuf>, timestamps: TimestampFormat, time_unit: TimeUnit) -> io::Result<Arc<Self>> {
+        if let Some(path) = &pid_file {
+            fs::write(path, format!("{}\n", std::process::id()))?;
+        }
+
+        let supervisor = Arc::new(Supervisor {
+            pid_file,
+            timestamps,
+            time_unit,
+            recheck: AtomicBool::new(false),
+            latest: Mutex::new((Vec::new(), HashMap::new())),
+        });
+
+        let mut signals = Signals::new([SIGHUP, SIGUSR1, SIGINT, SIGTERM])?;
+        let handler = Arc::clone(&supervisor);
+        thread::spawn(move || {
+            for signal in signals.forever() {
+                match signal {
+                    SIGHUP => handler.recheck.store(true, Ordering::SeqCst),
+                    SIGUSR1 => match handler.dump(Path::new(DUMP_PATH)) {
+                        Ok(()) => eprintln!("State dumped to {}", DUMP_PATH),
+                        Err(e) => eprintln!("Failed to dump state: {}", e),
+                    },
+                    _ => handler.exit(128 + signal),
+                }
+            }
+        });
+
+        Ok(supervisor)
+    }
+
+    /// Remember a result as the latest one for its URL
+    pub fn record(&self, status: &WebsiteStatus) {
+        let mut latest = self.latest.lock().unwrap();
+        let (order, results) = &mut *latest;
+        if results.insert(status.url.clone(), status.clone()).is_none() {
+            order.push(status.url.clone());
+        }
+    }
+
+    /// Forget the results of URLs no longer being checked
+    pub fn retain(&self, urls: &[String]) {
+        let mut latest = self.latest.lock().unwrap();
+        let (order, results) = &mut *latest;
+        order.retain(|url| urls.contains(url));
+        results.retain(|url, _| urls.contains(url));
+    }
+
+    /// Write the latest result of every URL seen so far
+    fn dump(&self, path: &Path) -> io::Result<()> {
+        let latest = self.latest.lock().unwrap();
+        let (order, results) = &*latest;
+        let results: Vec<_> = order.iter().map(|url| results[url].clone()).collect();
+        output::write_atomic(path, None, output::json_array(&results, self.timestamps, self.time_unit).as_bytes()).map(|_| ())
+    }
+
+    /// Wait up to `duration`, returning early when `SIGHUP` asks for a check.
+    /// Returns whether a `SIGHUP` arrived since the last call
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        while !self.recheck.swap(false, Ordering::SeqCst) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return false;
+            }
+            thread::sleep(remaining.min(Duration::from_millis(100)));
+        }
+        true
+    }
+
+    /// Remove the PID file and end the process
+    pub fn exit(&self, code: i32) -> ! {
+        if let Some(path) = &self.pid_file {
+            let _ = fs::remove_file(path);
+        }
+        std::process::exit(code);
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use hickory_resolver::{
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    error::{ResolveError, ResolveErrorKind},
+    Resolver,
+};
+
+/// Parse a resolver given as `IP` or `IP:PORT`, port 53 by default
+pub fn parse_resolver(input: &str) -> Option<SocketAddr> {
+    input
+        .parse::<SocketAddr>()
+        .ok()
+        .or_else(|| input.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, 53)))
+}
+
+/// Display name of a resolver, leaving out the default port
+pub fn resolver_name(resolver: &SocketAddr) -> String {
+    if resolver.port() == 53 {
+        resolver.ip().to_string()
+    } else {
+        resolver.to_string()
+    }
+}
+
+/// An empty answer is a valid result when comparing resolvers
+fn records_or_empty<T>(result: Result<Vec<T>, ResolveError>) -> Result<Vec<T>, String> {
+    match result {
+        Ok(records) => Ok(records),
+        Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => Ok(Vec::new()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// A resolver that sends every query to `server` once, without caching
+fn single_server(server: SocketAddr, timeout: Duration) -> Result<Resolver, String> {
+    let servers = NameServerConfigGroup::from_ips_clear(&[server.ip()], server.port(), true);
+    let mut options = ResolverOpts::default();
+    options.timeout = timeout;
+    options.attempts = 1;
+    options.cache_size = 0;
+    Resolver::new(ResolverConfig::from_parts(None, vec![], servers), options).map_err(|e| e.to_string())
+}
+
+/// A trailing dot keeps lookups from trying search domains
+fn absolute(host: &str) -> String {
+    format!("{}.", host.trim_end_matches('.'))
+}
+
+/// Query a single resolver for the A and AAAA records of `host`, returning
+/// every address sorted so answers from different resolvers compare equal
+pub fn lookup(resolver: SocketAddr, host: &str, timeout: Duration) -> Result<Vec<String>, String> {
+    let resolver = single_server(resolver, timeout)?;
+    let name = absolute(host);
+    let v4 = records_or_empty(
+        resolver.ipv4_lookup(name.as_str())
+            .map(|lookup| lookup.iter().map(|a| a.to_string()).collect()),
+    )?;
+    let v6 = records_or_empty(
+        resolver.ipv6_lookup(name.as_str())
+            .map(|lookup| lookup.iter().map(|a| a.to_string()).collect()),
+    )?;
+
+    let mut addresses: Vec<String> = v4.into_iter().chain(v6).collect();
+    addresses.sort();
+    addresses.dedup();
+    Ok(addresses)
+}
+
+/// Addresses of a host and the name server they came from
+#[derive(Debug, Clone)]
+pub struct Answer {
+    pub addresses: Vec<IpAddr>,
+    pub server: SocketAddr,
+}
+
+/// Resolve `host` with the first of `servers` that answers with at least
+/// one address, in place of the system resolver
+pub fn resolve(servers: &[SocketAddr], host: &str, timeout: Duration) -> Result<Answer, String> {
+    let name = absolute(host);
+    let mut errors = Vec::new();
+    for &server in servers {
+        let lookup = single_server(server, timeout).and_then(|resolver| {
+            resolver.lookup_ip(name.as_str()).map_err(|e| e.to_string())
+        });
+        match lookup {
+            Ok(lookup) => {
+                let addresses: Vec<IpAddr> = lookup.iter().collect();
+                if !addresses.is_empty() {
+                    return Ok(Answer { addresses, server });
+                }
+                errors.push(format!("{}: no addresses", resolver_name(&server)));
+            }
+            Err(e) => errors.push(format!("{}: {}", resolver_name(&server), e)),
+        }
+    }
+    Err(format!("DNS lookup of {} failed: {}", host, errors.join("; ")))
+}
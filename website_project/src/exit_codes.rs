@@ -0,0 +1,113 @@
+//! Process exit codes chosen by the kind of failure, so scripts can tell a
+//! DNS outage from a server error without parsing the results file.
+
+use crate::WebsiteStatus;
+
+/// Kinds of failed checks, worst first. When a round has failures of
+/// several kinds, the worst one with a code decides the exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FailureCategory {
+    /// The host name did not resolve
+    Dns,
+    /// The connection was refused, reset or closed
+    Connection,
+    /// TLS handshake or certificate verification failed
+    Tls,
+    /// The request or connection timed out
+    Timeout,
+    /// A 5xx status code
+    ServerError,
+    /// A 4xx status code
+    ClientError,
+    /// A 1xx or 3xx status code outside the accepted ones
+    OtherStatus,
+    /// Anything else, such as a failed content check on a 200 response
+    Error,
+}
+
+impl FailureCategory {
+    /// Categories in order of precedence, with their names in `--exit-code-map`
+    pub const ALL: [(FailureCategory, &'static str); 8] = [
+        (FailureCategory::Dns, "dns"),
+        (FailureCategory::Connection, "connection"),
+        (FailureCategory::Tls, "tls"),
+        (FailureCategory::Timeout, "timeout"),
+        (FailureCategory::ServerError, "5xx"),
+        (FailureCategory::ClientError, "4xx"),
+        (FailureCategory::OtherStatus, "status"),
+        (FailureCategory::Error, "error"),
+    ];
+
+    pub fn parse(name: &str) -> Option<FailureCategory> {
+        Self::ALL.iter().find(|(_, known)| *known == name).map(|(category, _)| *category)
+    }
+
+    /// Category of a failed check, `None` for a successful one. Transport
+    /// errors are told apart by their message, which for HTTP checks
+    /// includes the underlying cause
+    pub fn of(result: &WebsiteStatus) -> Option<FailureCategory> {
+        if result.success {
+            return None;
+        }
+        let error = match &result.action_status {
+            Ok(code) => {
+                return Some(match code {
+                    500..=599 => FailureCategory::ServerError,
+                    400..=499 => FailureCategory::ClientError,
+                    _ => FailureCategory::OtherStatus,
+                });
+            }
+            Err(e) => e.to_ascii_lowercase(),
+        };
+        let mentions = |words: &[&str]| words.iter().any(|word| error.contains(word));
+        Some(if mentions(&["timed out", "timeout"]) {
+            FailureCategory::Timeout
+        } else if mentions(&["dns error", "failed to lookup", "failed to resolve", "name or service not known", "no such host"]) {
+            FailureCategory::Dns
+        } else if mentions(&["certificate", "tls", "ssl"]) {
+            FailureCategory::Tls
+        } else if mentions(&["connection refused", "connection reset", "connection closed", "error trying to connect", "tcp connect error"]) {
+            FailureCategory::Connection
+        } else {
+            FailureCategory::Error
+        })
+    }
+}
+
+/// Exit codes by failure category, from `--exit-code-map 5xx=10,timeout=11`
+#[derive(Debug, Clone, Default)]
+pub struct ExitCodeMap {
+    codes: Vec<(FailureCategory, i32)>,
+}
+
+impl ExitCodeMap {
+    /// Parse comma separated `CATEGORY=CODE` pairs with codes from 1 to 255
+    pub fn parse(spec: &str) -> Result<ExitCodeMap, String> {
+        let mut codes = Vec::new();
+        for pair in spec.split(',') {
+            let (name, code) = pair.split_once('=').ok_or_else(|| format!("expected CATEGORY=CODE, got {:?}", pair))?;
+            let category = FailureCategory::parse(name.trim()).ok_or_else(|| {
+                let names: Vec<&str> = FailureCategory::ALL.iter().map(|(_, name)| *name).collect();
+                format!("unknown category {:?}, expected one of {}", name.trim(), names.join(", "))
+            })?;
+            let code = code.trim()
+                .parse()
+                .ok()
+                .filter(|code| (1..=255).contains(code))
+                .ok_or_else(|| format!("invalid exit code {:?}, expected 1 to 255", code.trim()))?;
+            codes.retain(|(known, _)| *known != category);
+            codes.push((category, code));
+        }
+        Ok(ExitCodeMap { codes })
+    }
+
+    /// Exit code for the worst failure category among `results` that has a
+    /// code, `None` when no failure has one
+    pub fn code(&self, results: &[WebsiteStatus]) -> Option<i32> {
+        results.iter()
+            .filter_map(FailureCategory::of)
+            .filter_map(|category| self.codes.iter().find(|(known, _)| *known == category))
+            .min_by_key(|(category, _)| *category)
+            .map(|(_, code)| *code)
+    }
+}
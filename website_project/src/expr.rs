@@ -0,0 +1,474 @@
+//! Success predicates such as `status >= 200 && status < 300 && time < 500`,
+//! evaluated against each result in place of the default success rule.
+//!
+//! Operands are result fields, numbers such as `-100`, quoted strings, `true`, `false` and
+//! `null`, compared with `==`, `!=`, `<`, `<=`, `>` and `>=` and combined with
+//! `&&`, `||`, `!` and parentheses. A field on its own tests whether it is
+//! set and not false or zero. Fields a result lacks, such as `status` after a
+//! connection error, are `null`, which orders against nothing.
+
+use std::{cmp::Ordering, fmt};
+
+use crate::WebsiteStatus;
+
+/// Fields an expression can refer to
+const FIELDS: [&str; 13] = [
+    "status",
+    "time",
+    "error",
+    "url",
+    "retries",
+    "requests",
+    "content_length",
+    "size_delta",
+    "cert_days",
+    "tls_version",
+    "version",
+    "link_count",
+    "page_title",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+impl Value {
+    fn truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(b) => *b,
+            Value::Number(n) => *n != 0.0,
+            Value::Text(s) => !s.is_empty(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Text(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn as_str(self) -> &'static str {
+        match self {
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        }
+    }
+
+    /// Equality works between any values, ordering only between two numbers
+    /// or two strings
+    fn apply(self, left: &Value, right: &Value) -> bool {
+        let ordering = match (left, right) {
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+            _ => None,
+        };
+        match self {
+            Op::Eq => left == right,
+            Op::Ne => left != right,
+            Op::Lt => ordering == Some(Ordering::Less),
+            Op::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            Op::Gt => ordering == Some(Ordering::Greater),
+            Op::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Field(&'static str),
+    Literal(Value),
+}
+
+impl Operand {
+    fn value(&self, status: &WebsiteStatus) -> Value {
+        match self {
+            Operand::Field(name) => field(name, status),
+            Operand::Literal(value) => value.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Field(name) => write!(f, "{}", name),
+            Operand::Literal(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Or(Vec<Node>),
+    And(Vec<Node>),
+    Not(Box<Node>),
+    Compare(Operand, Op, Operand),
+    Test(Operand),
+}
+
+impl Node {
+    fn evaluate(&self, status: &WebsiteStatus) -> bool {
+        match self {
+            Node::Or(nodes) => nodes.iter().any(|node| node.evaluate(status)),
+            Node::And(nodes) => nodes.iter().all(|node| node.evaluate(status)),
+            Node::Not(node) => !node.evaluate(status),
+            Node::Compare(left, op, right) => op.apply(&left.value(status), &right.value(status)),
+            Node::Test(operand) => operand.value(status).truthy(),
+        }
+    }
+
+    /// The smallest part of a failed node that explains the failure: the
+    /// first failing operand of an `&&`, the node itself otherwise
+    fn failure(&self, status: &WebsiteStatus) -> String {
+        if let Node::And(nodes) = self
+            && let Some(failed) = nodes.iter().find(|node| !node.evaluate(status))
+        {
+            return failed.failure(status);
+        }
+        let mut fields = Vec::new();
+        self.fields(&mut fields);
+        if fields.is_empty() {
+            return self.to_string();
+        }
+        let values: Vec<String> = fields.iter().map(|name| format!("{} = {}", name, field(name, status))).collect();
+        format!("{} ({})", self, values.join(", "))
+    }
+
+    /// Fields referred to, each once
+    fn fields(&self, fields: &mut Vec<&'static str>) {
+        let mut add = |operand: &Operand| {
+            if let Operand::Field(name) = operand
+                && !fields.contains(name)
+            {
+                fields.push(name);
+            }
+        };
+        match self {
+            Node::Or(nodes) | Node::And(nodes) => nodes.iter().for_each(|node| node.fields(fields)),
+            Node::Not(node) => node.fields(fields),
+            Node::Compare(left, _, right) => {
+                add(left);
+                add(right);
+            }
+            Node::Test(operand) => add(operand),
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Node::Or(nodes) => {
+                let parts: Vec<String> = nodes.iter().map(Node::to_string).collect();
+                write!(f, "{}", parts.join(" || "))
+            }
+            Node::And(nodes) => {
+                let parts: Vec<String> = nodes.iter()
+                    .map(|node| match node {
+                        Node::Or(_) => format!("({})", node),
+                        _ => node.to_string(),
+                    })
+                    .collect();
+                write!(f, "{}", parts.join(" && "))
+            }
+            Node::Not(node) => match **node {
+                Node::Not(_) | Node::Test(_) => write!(f, "!{}", node),
+                _ => write!(f, "!({})", node),
+            },
+            Node::Compare(left, op, right) => write!(f, "{} {} {}", left, op.as_str(), right),
+            Node::Test(operand) => write!(f, "{}", operand),
+        }
+    }
+}
+
+/// Value of a result field, `null` when the result does not have it
+fn field(name: &str, status: &WebsiteStatus) -> Value {
+    let number = |n: Option<f64>| n.map_or(Value::Null, Value::Number);
+    let text = |s: Option<&String>| s.map_or(Value::Null, |s| Value::Text(s.clone()));
+    match name {
+        "status" => number(status.action_status.as_ref().ok().map(|code| f64::from(*code))),
+        "time" => Value::Number(status.response_time.as_millis() as f64),
+        "error" => text(status.action_status.as_ref().err()),
+        "url" => Value::Text(status.url.clone()),
+        "retries" => Value::Number(f64::from(status.retries_used)),
+        "requests" => Value::Number(f64::from(status.requests_sent)),
+        "content_length" => number(status.content_length.map(|n| n as f64)),
+        "size_delta" => number(status.size_delta.map(|n| n as f64)),
+        "cert_days" => number(status.cert_expires_in_days.map(|n| n as f64)),
+        "tls_version" => text(status.tls_version.as_ref().and_then(|v| v.as_ref().ok())),
+        "version" => text(status.version_header_value.as_ref()),
+        "link_count" => number(status.link_count.map(f64::from)),
+        "page_title" => text(status.page_title.as_ref()),
+        _ => Value::Null,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Ident(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Minus,
+    Open,
+    Close,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        let mut next_is = |expected: char| chars.next_if_eq(&expected).is_some();
+        let token = match c {
+            _ if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' if next_is('&') => Token::And,
+            '|' if next_is('|') => Token::Or,
+            '=' if next_is('=') => Token::Op(Op::Eq),
+            '!' if next_is('=') => Token::Op(Op::Ne),
+            '!' => Token::Not,
+            '<' if next_is('=') => Token::Op(Op::Le),
+            '<' => Token::Op(Op::Lt),
+            '>' if next_is('=') => Token::Op(Op::Ge),
+            '>' => Token::Op(Op::Gt),
+            '-' => Token::Minus,
+            '"' | '\'' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        Some(ch) => text.push(ch),
+                        None => return Err("unterminated string".to_string()),
+                    }
+                }
+                Token::Text(text)
+            }
+            _ if c.is_ascii_digit() => {
+                let mut number = c.to_string();
+                while let Some(ch) = chars.next_if(|ch| ch.is_ascii_digit() || *ch == '.') {
+                    number.push(ch);
+                }
+                Token::Number(number.parse().map_err(|_| format!("invalid number {}", number))?)
+            }
+            _ if c.is_ascii_alphabetic() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some(ch) = chars.next_if(|ch| ch.is_ascii_alphanumeric() || *ch == '_') {
+                    ident.push(ch);
+                }
+                Token::Ident(ident)
+            }
+            '=' => return Err("use == to compare".to_string()),
+            _ => return Err(format!("unexpected character {:?}", c)),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over the tokens, `||` binding loosest and `!` tightest
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Node, String> {
+        let mut nodes = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            nodes.push(self.and()?);
+        }
+        Ok(if nodes.len() == 1 { nodes.remove(0) } else { Node::Or(nodes) })
+    }
+
+    fn and(&mut self) -> Result<Node, String> {
+        let mut nodes = vec![self.not()?];
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            nodes.push(self.not()?);
+        }
+        Ok(if nodes.len() == 1 { nodes.remove(0) } else { Node::And(nodes) })
+    }
+
+    fn not(&mut self) -> Result<Node, String> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            return Ok(Node::Not(Box::new(self.not()?)));
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.next();
+            let node = self.or()?;
+            if self.next() != Some(Token::Close) {
+                return Err("missing )".to_string());
+            }
+            return Ok(node);
+        }
+        let left = self.operand()?;
+        if let Some(Token::Op(op)) = self.peek().cloned() {
+            self.next();
+            return Ok(Node::Compare(left, op, self.operand()?));
+        }
+        Ok(Node::Test(left))
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Operand::Literal(Value::Number(n))),
+            Some(Token::Minus) => match self.next() {
+                Some(Token::Number(n)) => Ok(Operand::Literal(Value::Number(-n))),
+                _ => Err("expected a number after -".to_string()),
+            },
+            Some(Token::Text(s)) => Ok(Operand::Literal(Value::Text(s))),
+            Some(Token::Ident(ident)) => match ident.as_str() {
+                "true" => Ok(Operand::Literal(Value::Bool(true))),
+                "false" => Ok(Operand::Literal(Value::Bool(false))),
+                "null" => Ok(Operand::Literal(Value::Null)),
+                _ => FIELDS.iter()
+                    .find(|name| **name == ident)
+                    .map(|name| Operand::Field(name))
+                    .ok_or_else(|| format!("unknown field {}, expected one of {}", ident, FIELDS.join(", "))),
+            },
+            Some(token) => Err(format!("expected a field or value, found {:?}", token)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+/// A parsed success predicate
+#[derive(Debug)]
+pub struct SuccessExpr {
+    source: String,
+    root: Node,
+}
+
+impl SuccessExpr {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parser = Parser { tokens: tokenize(source)?, position: 0 };
+        let root = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("unexpected {:?} after the expression", token));
+        }
+        Ok(SuccessExpr { source: source.to_string(), root })
+    }
+
+    /// The expression as it was written
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// `Ok` when the result passes, otherwise the failed sub-condition with
+    /// the field values it saw, e.g. `time < 500 (time = 732)`
+    pub fn evaluate(&self, status: &WebsiteStatus) -> Result<(), String> {
+        if self.root.evaluate(status) {
+            Ok(())
+        } else {
+            Err(self.root.failure(status))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(code: u16, millis: u64) -> WebsiteStatus {
+        let mut status = WebsiteStatus::new("https://example.com");
+        status.action_status = Ok(code);
+        status.response_time = std::time::Duration::from_millis(millis);
+        status
+    }
+
+    fn passes(source: &str, status: &WebsiteStatus) -> bool {
+        SuccessExpr::parse(source).unwrap().evaluate(status).is_ok()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let ok = status(200, 900);
+        assert!(passes("status == 500 && time > 0 || status == 200", &ok));
+        assert!(!passes("status == 500 && (time > 0 || status == 200)", &ok));
+        assert!(passes("!(status == 500) && time > 500", &ok));
+    }
+
+    #[test]
+    fn compares_numbers_and_strings() {
+        let ok = status(200, 120);
+        assert!(passes("status >= 200 && status < 300", &ok));
+        assert!(passes("time <= 120 && time != 121", &ok));
+        assert!(passes("url == 'https://example.com'", &ok));
+        assert!(!passes("error == null && error < 1", &ok));
+    }
+
+    #[test]
+    fn parses_negative_numbers() {
+        let mut changed = status(200, 0);
+        changed.size_delta = Some(-250);
+        changed.cert_expires_in_days = Some(0);
+        assert!(passes("size_delta < -100", &changed));
+        assert!(passes("cert_days > -1", &changed));
+        assert!(!passes("size_delta >= -100", &changed));
+    }
+
+    #[test]
+    fn reports_the_failed_condition() {
+        let slow = status(200, 732);
+        let expr = SuccessExpr::parse("status == 200 && time < 500").unwrap();
+        assert_eq!(expr.evaluate(&slow).unwrap_err(), "time < 500 (time = 732)");
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        for (source, error) in [
+            ("status = 200", "use == to compare"),
+            ("status == 'ok", "unterminated string"),
+            ("(status == 200", "missing )"),
+            ("status ==", "unexpected end of expression"),
+            ("status > -time", "expected a number after -"),
+            ("latency < 5", "unknown field latency"),
+            ("status == 200 200", "unexpected Number(200.0) after the expression"),
+        ] {
+            let message = SuccessExpr::parse(source).unwrap_err();
+            assert!(message.starts_with(error), "{}: {}", source, message);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Content checks on HTML response bodies.
+
+use reqwest::Url;
+use scraper::{Html, Selector};
+use serde_json::Value;
+
+/// Number of `<a href>` elements in a document
+pub fn link_count(document: &Html) -> u32 {
+    let links = Selector::parse("a[href]").expect("valid selector");
+    document.select(&links).count() as u32
+}
+
+/// Number of images without alt text: `<img>` elements whose `alt` is
+/// missing or blank, and inline `<svg role="img">` elements with neither
+/// `aria-label` nor `aria-labelledby`
+pub fn images_missing_alt(document: &Html) -> u32 {
+    let images = Selector::parse(r#"img, svg[role="img" i]"#).expect("valid selector");
+    let blank = |value: Option<&str>| value.is_none_or(|value| value.trim().is_empty());
+    document.select(&images)
+        .filter(|image| {
+            let image = image.value();
+            if image.name() == "img" {
+                blank(image.attr("alt"))
+            } else {
+                blank(image.attr("aria-label")) && blank(image.attr("aria-labelledby"))
+            }
+        })
+        .count() as u32
+}
+
+/// Cross-origin scripts, stylesheets and preloads without an `integrity`
+/// attribute, resolved against `page` and each listed once in document
+/// order. Other `<link>`s, such as icons, cannot carry one
+pub fn missing_sri(document: &Html, page: &Url) -> Vec<String> {
+    let resources = Selector::parse(
+        r#"script[src], link[rel~="stylesheet" i][href], link[rel~="preload" i][href], link[rel~="modulepreload" i][href]"#,
+    )
+    .expect("valid selector");
+    let mut missing = Vec::new();
+    for element in document.select(&resources) {
+        let element = element.value();
+        if element.attr("integrity").is_some_and(|integrity| !integrity.trim().is_empty()) {
+            continue;
+        }
+        let target = element.attr("src").or(element.attr("href")).unwrap_or_default().trim();
+        let Ok(url) = page.join(target) else {
+            continue;
+        };
+        let external = matches!(url.scheme(), "http" | "https") && url.origin() != page.origin();
+        if external && !missing.contains(&url.to_string()) {
+            missing.push(url.to_string());
+        }
+    }
+    missing
+}
+
+/// `href` of the first `<link rel="canonical">` element
+pub fn canonical(document: &Html) -> Option<String> {
+    let canonical = Selector::parse(r#"link[rel~="canonical" i][href]"#).expect("valid selector");
+    let element = document.select(&canonical).next()?;
+    element.value().attr("href").map(|href| href.trim().to_string())
+}
+
+/// `content` of the `<meta property="...">` element for `property`, `None`
+/// when the element is missing or its content is blank
+pub fn meta_property(document: &Html, property: &str) -> Option<String> {
+    meta_content(document, "property", property)
+}
+
+/// `content` of the `<meta name="...">` element for `name`, like
+/// [`meta_property`]
+pub fn meta_name(document: &Html, name: &str) -> Option<String> {
+    meta_content(document, "name", name)
+}
+
+fn meta_content(document: &Html, attribute: &str, value: &str) -> Option<String> {
+    let meta = Selector::parse(&format!(r#"meta[{}="{}" i][content]"#, attribute, value)).expect("valid selector");
+    let content = document.select(&meta).next()?.value().attr("content")?.trim();
+    (!content.is_empty()).then(|| content.to_string())
+}
+
+/// What is wrong with a viewport meta tag's content for mobile devices, if
+/// anything: it must set `width=device-width` and, with `initial_scale`,
+/// `initial-scale=1`
+pub fn viewport_problem(content: Option<&str>, initial_scale: bool) -> Option<String> {
+    let Some(content) = content else {
+        return Some("page has no viewport meta tag".to_string());
+    };
+    let settings: Vec<(String, String)> = content.split([',', ';'])
+        .filter_map(|setting| setting.split_once('='))
+        .map(|(key, value)| (key.trim().to_ascii_lowercase(), value.trim().to_ascii_lowercase()))
+        .collect();
+    let has = |key: &str, matches: &dyn Fn(&str) -> bool| settings.iter().any(|(k, v)| k == key && matches(v));
+    let mut missing = Vec::new();
+    if !has("width", &|width| width == "device-width") {
+        missing.push("width=device-width");
+    }
+    if initial_scale && !has("initial-scale", &|scale| scale.parse::<f64>() == Ok(1.0)) {
+        missing.push("initial-scale=1");
+    }
+    (!missing.is_empty()).then(|| format!("viewport meta tag {:?} lacks {}", content, missing.join(" and ")))
+}
+
+/// Schema types declared by JSON-LD blocks and microdata `itemtype`s, each
+/// once in document order, and whether any JSON-LD block failed to parse.
+/// Microdata types are cut to their last path segment, so
+/// `https://schema.org/Product` is `Product` like its JSON-LD counterpart
+pub fn structured_data(document: &Html) -> (Vec<String>, bool) {
+    let mut types = Vec::new();
+    let mut invalid = false;
+    let scripts = Selector::parse(r#"script[type="application/ld+json"]"#).expect("valid selector");
+    for script in document.select(&scripts) {
+        match serde_json::from_str::<Value>(&script.text().collect::<String>()) {
+            Ok(value) => json_ld_types(&value, &mut types),
+            Err(_) => invalid = true,
+        }
+    }
+    let items = Selector::parse("[itemscope][itemtype]").expect("valid selector");
+    for item in document.select(&items) {
+        for itemtype in item.value().attr("itemtype").unwrap_or_default().split_whitespace() {
+            let name = itemtype.trim_end_matches('/').rsplit('/').next().unwrap_or(itemtype);
+            add_type(&mut types, name);
+        }
+    }
+    (types, invalid)
+}
+
+/// Collect `@type` values anywhere in a JSON-LD document, which covers
+/// `@graph` lists and nested entities
+fn json_ld_types(value: &Value, types: &mut Vec<String>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                match (key.as_str(), value) {
+                    ("@type", Value::String(name)) => add_type(types, name),
+                    ("@type", Value::Array(names)) => {
+                        names.iter().filter_map(Value::as_str).for_each(|name| add_type(types, name));
+                    }
+                    _ => json_ld_types(value, types),
+                }
+            }
+        }
+        Value::Array(values) => values.iter().for_each(|value| json_ld_types(value, types)),
+        _ => {}
+    }
+}
+
+fn add_type(types: &mut Vec<String>, name: &str) {
+    if !name.is_empty() && !types.iter().any(|known| known == name) {
+        types.push(name.to_string());
+    }
+}
+
+/// Text of the `<title>` element with whitespace runs collapsed, `None` when
+/// the page has none
+pub fn title(document: &Html) -> Option<String> {
+    let title = Selector::parse("title").expect("valid selector");
+    let element = document.select(&title).next()?;
+    Some(element.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" "))
+}
@@ -0,0 +1,178 @@
+use std::{
+    io::ErrorKind,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::atomic::{AtomicU16, Ordering},
+    time::{Duration, Instant},
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+const ECHO_REQUEST_V4: u8 = 8;
+const ECHO_REPLY_V4: u8 = 0;
+const ECHO_REQUEST_V6: u8 = 128;
+const ECHO_REPLY_V6: u8 = 129;
+const PAYLOAD: &[u8; 32] = b"website_checker icmp echo probe!";
+
+/// Sequence numbers are shared by all workers so concurrent pings never
+/// mistake each other's replies for their own
+static SEQUENCE: AtomicU16 = AtomicU16::new(0);
+
+/// Open a raw ICMP socket for the given address family
+fn open_socket(ipv6: bool) -> Result<Socket, String> {
+    let (domain, protocol) = if ipv6 {
+        (Domain::IPV6, Protocol::ICMPV6)
+    } else {
+        (Domain::IPV4, Protocol::ICMPV4)
+    };
+
+    Socket::new(domain, Type::RAW, Some(protocol)).map_err(|e| match e.kind() {
+        ErrorKind::PermissionDenied => {
+            "ICMP ping requires root or the CAP_NET_RAW capability".to_string()
+        }
+        _ => format!("failed to open ICMP socket: {}", e),
+    })
+}
+
+/// Verify the process may open raw ICMP sockets before any check runs
+pub fn check_privileges() -> Result<(), String> {
+    open_socket(false).map(|_| ())
+}
+
+/// Internet checksum (RFC 1071) over an ICMP message
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum = data
+        .chunks(2)
+        .map(|pair| u32::from(pair[0]) << 8 | u32::from(*pair.get(1).unwrap_or(&0)))
+        .sum::<u32>();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Send a single ICMP echo request to `host` and return the round-trip time
+pub fn ping(host: &str, timeout: Duration) -> Result<Duration, String> {
+    let addr = (host, 0)
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or("failed to resolve domain")?;
+    let ipv6 = addr.is_ipv6();
+
+    let socket = UdpSocket::from(open_socket(ipv6)?);
+    let identifier = std::process::id() as u16;
+    let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+
+    let mut packet = vec![if ipv6 { ECHO_REQUEST_V6 } else { ECHO_REQUEST_V4 }, 0, 0, 0];
+    packet.extend_from_slice(&identifier.to_be_bytes());
+    packet.extend_from_slice(&sequence.to_be_bytes());
+    packet.extend_from_slice(PAYLOAD);
+    // The kernel fills in the checksum for ICMPv6 raw sockets
+    if !ipv6 {
+        let sum = checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+
+    let start = Instant::now();
+    socket
+        .send_to(&packet, SocketAddr::new(addr.ip(), 0))
+        .map_err(|e| e.to_string())?;
+
+    let mut buf = [0u8; 1500];
+    loop {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            return Err("no reply".to_string());
+        }
+        socket.set_read_timeout(Some(remaining)).map_err(|e| e.to_string())?;
+
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                return Err("no reply".to_string());
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let message = icmp_message(&buf[..len], from.ip().is_ipv4());
+        if is_echo_reply(message, ipv6, identifier, sequence) {
+            return Ok(start.elapsed());
+        }
+    }
+}
+
+/// The ICMP message of a packet read from a raw socket. IPv4 raw sockets
+/// deliver the IP header in front of it
+fn icmp_message(packet: &[u8], ipv4: bool) -> &[u8] {
+    if !ipv4 {
+        return packet;
+    }
+    let header_len = packet.first().map_or(0, |first| usize::from(first & 0x0f) * 4);
+    &packet[header_len.min(packet.len())..]
+}
+
+/// Whether `message` answers the echo request with `identifier` and
+/// `sequence`. Raw sockets see every ICMP message the host receives
+fn is_echo_reply(message: &[u8], ipv6: bool, identifier: u16, sequence: u16) -> bool {
+    let expected = if ipv6 { ECHO_REPLY_V6 } else { ECHO_REPLY_V4 };
+    message.len() >= 8
+        && message[0] == expected
+        && message[4..6] == identifier.to_be_bytes()
+        && message[6..8] == sequence.to_be_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An echo reply behind a minimal IPv4 header
+    fn ipv4_reply(identifier: u16, sequence: u16) -> Vec<u8> {
+        let mut packet = vec![0x45];
+        packet.resize(20, 0);
+        packet.extend_from_slice(&[ECHO_REPLY_V4, 0, 0, 0]);
+        packet.extend_from_slice(&identifier.to_be_bytes());
+        packet.extend_from_slice(&sequence.to_be_bytes());
+        packet.extend_from_slice(PAYLOAD);
+        packet
+    }
+
+    #[test]
+    fn checksum_matches_rfc_1071_example() {
+        assert_eq!(checksum(&[0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7]), !0xddf2);
+    }
+
+    #[test]
+    fn checksum_pads_odd_lengths() {
+        assert_eq!(checksum(&[0x12, 0x34, 0x56]), checksum(&[0x12, 0x34, 0x56, 0x00]));
+    }
+
+    #[test]
+    fn checksummed_message_sums_to_zero() {
+        let mut packet = vec![ECHO_REQUEST_V4, 0, 0, 0, 0x12, 0x34, 0x00, 0x07];
+        packet.extend_from_slice(PAYLOAD);
+        let sum = checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+        assert_eq!(checksum(&packet), 0);
+    }
+
+    #[test]
+    fn matches_own_reply_behind_ipv4_header() {
+        let packet = ipv4_reply(0x1234, 7);
+        assert!(is_echo_reply(icmp_message(&packet, true), false, 0x1234, 7));
+    }
+
+    #[test]
+    fn ignores_replies_to_other_requests() {
+        let packet = ipv4_reply(0x1234, 7);
+        let message = icmp_message(&packet, true);
+        assert!(!is_echo_reply(message, false, 0x1234, 8));
+        assert!(!is_echo_reply(message, false, 0x4321, 7));
+        assert!(!is_echo_reply(message, true, 0x1234, 7));
+    }
+
+    #[test]
+    fn ignores_truncated_packets() {
+        assert!(icmp_message(&[0x45, 0, 0], true).is_empty());
+        assert!(!is_echo_reply(&[ECHO_REPLY_V6, 0, 0, 0, 0x12], true, 0x1234, 0));
+    }
+}
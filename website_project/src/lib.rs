@@ -0,0 +1,47 @@
+//! Concurrent website status checks.
+//!
+//! [`run`] checks a list of targets on a pool of worker threads and hands
+//! each [`WebsiteStatus`] to a callback as soon as it completes, so
+//! embedders can react to results while the scan is still going.
+
+mod bodies;
+mod checker;
+mod config;
+pub mod connections;
+pub mod daemon;
+pub mod dns;
+pub mod exit_codes;
+mod expr;
+mod html;
+mod icmp;
+pub mod limits;
+pub mod load;
+pub mod merge;
+pub mod random;
+pub mod output;
+pub mod prometheus;
+pub mod report;
+mod schema;
+mod smtp;
+pub mod soft404;
+pub mod ssh;
+pub mod statsd;
+mod status;
+mod summary;
+pub mod targets;
+pub mod theme;
+mod throttle;
+mod timestamp;
+mod timing;
+pub mod tls;
+pub mod webhook;
+mod websocket;
+
+pub use checker::run;
+pub use config::{parse_status_ranges, BodyStoreFormat, Config};
+pub use expr::SuccessExpr;
+pub use schema::JsonSchema;
+pub use status::{CachePolicy, CookieSecurity, ExpectCt, LatencyStats, ProbeResult, WebsiteStatus};
+pub use summary::Summary;
+pub use timestamp::{TimeUnit, TimestampFormat};
+pub use timing::TimingBreakdown;
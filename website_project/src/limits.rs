@@ -0,0 +1,99 @@
+//! The open file limit, checked before a scan so one too wide for
+//! `ulimit -n` is caught at startup instead of failing halfway through with
+//! "too many open files".
+
+use std::{error::Error, io};
+
+/// Error recorded for checks that could not open a connection because the
+/// process ran out of file descriptors
+pub const TOO_MANY_OPEN_FILES: &str = "too many open files";
+
+/// Descriptors a scan of `workers` threads is expected to need: a few per
+/// worker for its connection, DNS lookups and TLS inspection, plus headroom
+/// for stdio, output files and the resolver
+pub fn required(workers: usize) -> u64 {
+    workers as u64 * 3 + 64
+}
+
+/// Soft and hard limit on open files, `u64::MAX` (`RLIM_INFINITY`) for
+/// unlimited
+pub fn open_files() -> io::Result<(u64, u64)> {
+    let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((limit.rlim_cur, limit.rlim_max))
+}
+
+/// Raise the soft limit to `target`, capped at the hard limit. Returns the
+/// new soft limit; a limit already above `target` is left alone
+pub fn raise(target: u64) -> io::Result<u64> {
+    let (soft, hard) = open_files()?;
+    let target = target.min(hard);
+    if target <= soft {
+        return Ok(soft);
+    }
+    let limit = libc::rlimit { rlim_cur: target, rlim_max: hard };
+    if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(target)
+}
+
+/// Whether an error, or any error behind it, is `EMFILE` or its
+/// system-wide counterpart `ENFILE`. reqwest reports both as a plain "error
+/// sending request" with the cause buried in its sources
+pub fn is_too_many_open_files(error: &(dyn Error + 'static)) -> bool {
+    let mut current = Some(error);
+    while let Some(error) = current {
+        if let Some(io) = error.downcast_ref::<io::Error>()
+            && matches!(io.raw_os_error(), Some(libc::EMFILE | libc::ENFILE))
+        {
+            return true;
+        }
+        current = error.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+
+    use super::*;
+
+    /// An error that hides its cause behind `source()`, as reqwest does
+    #[derive(Debug)]
+    struct Wrapped(io::Error);
+
+    impl fmt::Display for Wrapped {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("error sending request")
+        }
+    }
+
+    impl Error for Wrapped {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    fn wrapped(code: i32) -> Wrapped {
+        Wrapped(io::Error::from_raw_os_error(code))
+    }
+
+    #[test]
+    fn recognizes_emfile_behind_sources() {
+        assert!(is_too_many_open_files(&wrapped(libc::EMFILE)));
+    }
+
+    #[test]
+    fn recognizes_enfile_behind_sources() {
+        assert!(is_too_many_open_files(&wrapped(libc::ENFILE)));
+    }
+
+    #[test]
+    fn ignores_other_os_errors() {
+        assert!(!is_too_many_open_files(&wrapped(libc::ECONNREFUSED)));
+    }
+}
@@ -0,0 +1,142 @@
+//! Sustained load: the URL list requested round-robin at a fixed rate for
+//! a fixed time, with latency percentiles over the whole window.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    checker::{check_target, prepare, ScanState},
+    status::escape_json,
+    summary::percentile,
+    throttle::TokenBucket,
+    Config,
+};
+
+/// Latency over every request of a load run, for one URL or all of them
+#[derive(Debug, Clone)]
+pub struct LoadStats {
+    pub url: String,
+    pub requests: usize,
+    /// Requests that did not count as a success
+    pub failures: usize,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+impl LoadStats {
+    fn from_samples(url: &str, samples: &[(Duration, bool)]) -> Self {
+        let mut times: Vec<Duration> = samples.iter().map(|(time, _)| *time).collect();
+        times.sort();
+        LoadStats {
+            url: url.to_string(),
+            requests: samples.len(),
+            failures: samples.iter().filter(|(_, success)| !success).count(),
+            p50: percentile(&times, 50.0),
+            p95: percentile(&times, 95.0),
+            p99: percentile(&times, 99.0),
+        }
+    }
+
+    pub fn to_json_string(&self) -> String {
+        format!(
+            "{{\"url\": \"{}\", \"requests\": {}, \"failures\": {}, \"response_time_p50_ms\": {}, \"response_time_p95_ms\": {}, \"response_time_p99_ms\": {}}}",
+            escape_json(&self.url),
+            self.requests,
+            self.failures,
+            self.p50.as_millis(),
+            self.p95.as_millis(),
+            self.p99.as_millis()
+        )
+    }
+}
+
+/// Outcome of a load run
+#[derive(Debug, Clone)]
+pub struct LoadReport {
+    pub elapsed: Duration,
+    pub target_rps: f64,
+    /// One entry per URL, in input order
+    pub per_url: Vec<LoadStats>,
+    /// Every request of the run together, with `url` set to `"all"`
+    pub total: LoadStats,
+}
+
+impl LoadReport {
+    /// Requests actually completed per second, below the target when the
+    /// workers could not keep up
+    pub fn achieved_rps(&self) -> f64 {
+        self.total.requests as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    pub fn to_json_string(&self) -> String {
+        format!(
+            r#"{{
+        "duration_s": {:.1},
+        "target_rps": {},
+        "achieved_rps": {:.1},
+        "total": {},
+        "urls": [{}]
+    }}"#,
+            self.elapsed.as_secs_f64(),
+            self.target_rps,
+            self.achieved_rps(),
+            self.total.to_json_string(),
+            self.per_url.iter()
+                .map(LoadStats::to_json_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+/// Request `urls` round-robin on `config.workers` threads, starting at most
+/// `target_rps` requests per second, until `duration` has passed. Each
+/// request is a full check, so retries and extra probes count against the
+/// worker that runs it but not against the rate
+pub fn run(urls: &[String], config: &Config, duration: Duration, target_rps: f64) -> Result<LoadReport, String> {
+    if urls.is_empty() {
+        return Err("no URLs to load".to_string());
+    }
+    let (clients, config) = prepare(urls, config)?;
+    let state = ScanState::default();
+    let bucket = TokenBucket::new(target_rps);
+    let next = AtomicUsize::new(0);
+    let samples: Mutex<Vec<Vec<(Duration, bool)>>> = Mutex::new(vec![Vec::new(); urls.len()]);
+
+    let start = Instant::now();
+    let deadline = start + duration;
+    thread::scope(|scope| {
+        for _ in 0..config.workers {
+            scope.spawn(|| {
+                while Instant::now() < deadline {
+                    bucket.acquire();
+                    if Instant::now() >= deadline {
+                        break;
+                    }
+                    let index = next.fetch_add(1, Ordering::Relaxed) % urls.len();
+                    let status = check_target(&clients, &urls[index], &config, &state);
+                    samples.lock().unwrap()[index].push((status.response_time, status.success));
+                }
+            });
+        }
+    });
+
+    let samples = samples.into_inner().unwrap();
+    let all: Vec<_> = samples.iter().flatten().copied().collect();
+    Ok(LoadReport {
+        elapsed: start.elapsed(),
+        target_rps,
+        per_url: urls.iter()
+            .zip(&samples)
+            .map(|(url, samples)| LoadStats::from_samples(url, samples))
+            .collect(),
+        total: LoadStats::from_samples("all", &all),
+    })
+}
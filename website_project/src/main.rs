@@ -1,247 +1,2526 @@
 use std::{
+    collections::{BTreeMap, HashMap},
     env,
-    fs::File,
-    io::{BufRead, BufReader, Write},
-    path::PathBuf,
-    sync::{mpsc, Arc, Mutex},
-    thread,
+    io::{IsTerminal, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
     time::{Duration, Instant, SystemTime},
 };
 
-use reqwest::blocking::Client;
+use chrono_tz::Tz;
+use regex::Regex;
+use reqwest::Method;
+use semver::VersionReq;
+use serde::{Deserialize, Serialize};
+use website_project::{
+    connections,
+    daemon::Supervisor,
+    exit_codes::ExitCodeMap,
+    dns, limits,
+    merge::{self, MergeStrategy},
+    random::Rng,
+    output::{self, CsvWriter, Round},
+    parse_status_ranges, prometheus,
+    report::{self, ReportTemplate},
+    soft404, ssh,
+    statsd::StatsdClient,
+    targets::{self, Shard},
+    theme::{self, ColorTheme},
+    tls, webhook, BodyStoreFormat, Config, JsonSchema, SuccessExpr, Summary, TimeUnit, TimestampFormat, WebsiteStatus,
+};
+
+/// Request headers sent with `--simulate-mobile`: Chrome on a typical
+/// Android phone and client hints for a 375px wide, 2x density screen
+const MOBILE_HEADERS: [(&str, &str); 3] = [
+    (
+        "User-Agent",
+        "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Mobile Safari/537.36",
+    ),
+    ("Viewport-Width", "375"),
+    ("DPR", "2"),
+];
+
+/// Print usage instructions and exit
+fn print_usage() -> ! {
+    eprintln!("Usage: website_checker [--config FILE] [--file sites.txt ...] [URL ...]");
+    eprintln!("       website_checker print-config|--print-config [--print-config-format toml|json] [options]");
+    eprintln!("       website_checker --merge RESULTS.json ... [--merge-strategy all|latest]");
+    eprintln!("                       [--output|-o PATH|-] [--summary-file PATH]");
+    eprintln!("       website_checker check-version URL ... --version-header NAME --expected-version REQ");
+    eprintln!("       [--shard INDEX/TOTAL] [--recheck RESULTS.json] [--preflight-head-then-get]");
+    eprintln!("       [--seed N] [--check-schema-version SCHEMA.json] [--stdin-json [--validate-schema SCHEMA.json]]");
+    eprintln!("       [--capture-error-body] [--max-body-size BYTES] [--min-response-time MS] [--fail-too-fast]");
+    eprintln!("       [--parallel-files] [--workers N] [--max-workers N] [--per-ip-limit N] [--ramp-up S] [--ordered]");
+    eprintln!("       [--timeout S] [--time-limit-per-domain DOMAIN=S ...] [--retries N]");
+    eprintln!("       [--tls-info] [--min-tls VERSION] [--summary-file PATH]");
+    eprintln!("       [--icmp-ping HOST ...] [--success-range LOW-HIGH ...] [--success-expr EXPR]");
+    eprintln!("       [--smtp-check HOST:PORT ...] [--smtp-ehlo] [--output-gzip]");
+    eprintln!("       [--host-time-budget S] [--ssh-check HOST:PORT ...]");
+    eprintln!("       [--expect-ssh-version MAJOR.MINOR] [--header 'NAME: VALUE' ...] [--simulate-mobile]");
+    eprintln!("       [--include-request-info] [--no-redact] [--validate]");
+    eprintln!("       [--dns-check HOST ...] [--dns-resolvers IP,IP,...] [--dns-server IP ...] [--verbose|-v]");
+    eprintln!("       [--html-template FILE] [--detailed-timing] [--check-ssl-expiry-days N]");
+    eprintln!("       [--report-template FILE --report-out FILE]");
+    eprintln!("       [--retry-on-empty-body] [--request-count N] [--checkpoint-every S]");
+    eprintln!("       [--check-response-not-empty] [--min-response-bytes N] [--disable-compression]");
+    eprintln!("       [--interval S] [--probe-interval-per-url] [--rounds N] [--series]");
+    eprintln!("       [--connection-reuse-stats]");
+    eprintln!("       [--host-allowlist HOST,...] [--host-denylist HOST,...]");
+    eprintln!("       [--retry-on-error-matching REGEX] [--retry-on-http2-goaway] [--write-pid FILE] [--gzip-level 0-9]");
+    eprintln!("       [--probe-paths PATH,...] [--probe-mode all|any] [--summary-only]");
+    eprintln!("       [--timestamp-format unix|rfc3339] [--timezone TZ] [--time-unit ms|us|s]");
+    eprintln!("       [--color-theme default|colorblind|mono]");
+    eprintln!("       [--method METHOD] [--retry-non-idempotent] [--retry-rate N]");
+    eprintln!("       [--version-header NAME] [--expected-version REQ] [--source-ip IP]");
+    eprintln!("       [--max-results-per-file N] [--track-size STATE] [--size-change-threshold PCT]");
+    eprintln!("       [--output-format json|logfmt|ndjson|graphite|csv] [--output PATH|-] [--run-id ID] [--append]");
+    eprintln!("       [--output-rotate-size BYTES] [--output-rotate-count N] [--require-up N]");
+    eprintln!("       [--indent N|tab]");
+    eprintln!("       [--graphite-prefix PREFIX] [--graphite-host HOST:PORT]");
+    eprintln!("       [--csv-delimiter CHAR] [--csv-always-quote]");
+    eprintln!("       [--fail-on-cert-expiry-within DAYS] [--check-hpkp] [--require-expect-ct-enforce]");
+    eprintln!("       [--check-cache-headers] [--require-cacheable]");
+    eprintln!("       [--report-cookie-security] [--require-cookie-secure] [--max-age S]");
+    eprintln!("       [--check-x-frame-options] [--require-x-frame-options]");
+    eprintln!("       [--per-worker-output DIR] [--normalize-trailing-slash] [--statsd HOST:PORT]");
+    eprintln!("       [--recovery-webhook URL] [--check-link-count MIN] [--max-link-count MAX]");
+    eprintln!("       [--pushgateway URL] [--job NAME] [--require-push] [--exit-code-map CATEGORY=CODE,...]");
+    eprintln!("       [--verify-output]");
+    eprintln!("       [--load-duration S --target-rps N] [--check-title TEXT] [--check-title-regex]");
+    eprintln!("       [--check-title-case-sensitive] [--check-canonical] [--detect-soft-404]");
+    eprintln!("       [--check-open-graph] [--require-og-image-https] [--max-file-descriptors N|max]");
+    eprintln!("       [--check-structured-data] [--require-structured-data-type TYPE ...]");
+    eprintln!("       [--check-viewport-meta] [--require-viewport-initial-scale] [--check-image-alt] [--require-image-alt]");
+    eprintln!("       [--check-sri] [--require-sri]");
+    eprintln!("       [--save-bodies DIR] [--body-store-format raw|with-headers] [--abort-on-cert-error-for-host]");
+    eprintln!();
+    eprintln!("--dns-server resolves the hosts of the URLs given; hosts reached only through redirects,");
+    eprintln!("and non-HTTP checks, still use the system resolver.");
+    std::process::exit(2);
+}
+
+/// Layout of the results file
+#[derive(Clone, Copy)]
+enum OutputFormat {
+    Json,
+    /// One `key=value` line per result
+    Logfmt,
+    /// One JSON object per line, tagged with the run ID
+    Ndjson,
+    /// Graphite plaintext metric lines
+    Graphite,
+    /// A header row and one record per result
+    Csv,
+}
+
+/// Options of the front end that are not part of the scan `Config`, with
+/// the values they take on the command line and durations in seconds. The
+/// `[cli]` table of a config file holds them, so `print-config` output
+/// reproduces the whole run. URLs, URL files and one-off modes such as
+/// `--merge` are left out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct Settings {
+    parallel_files: bool,
+    min_tls: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary_file: Option<PathBuf>,
+    summary_only: bool,
+    output_format: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<PathBuf>,
+    append: bool,
+    output_gzip: bool,
+    gzip_level: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output_rotate_size: Option<u64>,
+    output_rotate_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_results_per_file: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    per_worker_output: Option<PathBuf>,
+    verify_output: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    indent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run_id: Option<String>,
+    csv_delimiter: String,
+    csv_always_quote: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    graphite_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    graphite_host: Option<String>,
+    timestamp_format: String,
+    timezone: String,
+    time_unit: String,
+    color_theme: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    html_template: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report_template: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report_out: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checkpoint_every: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interval: Option<u64>,
+    probe_interval_per_url: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rounds: Option<u64>,
+    series: bool,
+    connection_reuse_stats: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    require_up: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fail_on_cert_expiry_within: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    track_size: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shard: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    load_duration: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_rps: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recovery_webhook: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pushgateway: Option<String>,
+    job: String,
+    require_push: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exit_code_map: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    statsd: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    write_pid: Option<PathBuf>,
+    /// A number, or `max` for the hard limit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_file_descriptors: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            parallel_files: false,
+            min_tls: "TLSv1.2".to_string(),
+            summary_file: None,
+            summary_only: false,
+            output_format: "json".to_string(),
+            output: None,
+            append: false,
+            output_gzip: false,
+            gzip_level: output::DEFAULT_GZIP_LEVEL,
+            output_rotate_size: None,
+            output_rotate_count: 5,
+            max_results_per_file: None,
+            per_worker_output: None,
+            verify_output: false,
+            indent: None,
+            run_id: None,
+            csv_delimiter: ",".to_string(),
+            csv_always_quote: false,
+            graphite_prefix: None,
+            graphite_host: None,
+            timestamp_format: "unix".to_string(),
+            timezone: "UTC".to_string(),
+            time_unit: "ms".to_string(),
+            color_theme: "default".to_string(),
+            html_template: None,
+            report_template: None,
+            report_out: None,
+            checkpoint_every: None,
+            interval: None,
+            probe_interval_per_url: false,
+            rounds: None,
+            series: false,
+            connection_reuse_stats: false,
+            require_up: None,
+            fail_on_cert_expiry_within: None,
+            track_size: None,
+            shard: None,
+            load_duration: None,
+            target_rps: None,
+            recovery_webhook: None,
+            pushgateway: None,
+            job: "website_checker".to_string(),
+            require_push: false,
+            exit_code_map: None,
+            statsd: None,
+            write_pid: None,
+            max_file_descriptors: None,
+        }
+    }
+}
+
+impl Settings {
+    /// A copy safe to print: the webhook and Pushgateway URLs keep only
+    /// their scheme, host and port, since tokens tend to sit in the rest
+    fn redacted(&self) -> Settings {
+        Settings {
+            recovery_webhook: self.recovery_webhook.as_deref().map(redact_url),
+            pushgateway: self.pushgateway.as_deref().map(redact_url),
+            ..self.clone()
+        }
+    }
+}
+
+/// `url` with its user info, path and query replaced by `[REDACTED]`
+fn redact_url(url: &str) -> String {
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        return "[REDACTED]".to_string();
+    };
+    let mut redacted = format!("{}://", parsed.scheme());
+    if !parsed.username().is_empty() || parsed.password().is_some() {
+        redacted.push_str("[REDACTED]@");
+    }
+    redacted.push_str(parsed.host_str().unwrap_or_default());
+    if let Some(port) = parsed.port() {
+        redacted.push_str(&format!(":{}", port));
+    }
+    if !matches!(parsed.path(), "" | "/") {
+        redacted.push_str("/[REDACTED]");
+    }
+    if parsed.query().is_some() {
+        redacted.push_str("?[REDACTED]");
+    }
+    redacted
+}
+
+/// A config file: the scan `Config` at the top level and the front end's
+/// `Settings` in its `[cli]` table
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ConfigFile {
+    cli: Settings,
+}
+
+/// Command line settings, the scan `Config` plus everything that only
+/// concerns how the front end reports results
+struct Cli {
+    config: Config,
+    /// URL files, re-read on `SIGHUP` in interval mode
+    file_paths: Vec<PathBuf>,
+    /// Number of URLs given on the command line, these precede the files'
+    argument_count: usize,
+    /// Index into `file_paths` of each URL after the command line ones
+    file_groups: Vec<usize>,
+    /// `interval=S` annotation of each URL after the command line ones
+    file_intervals: Vec<Option<Duration>>,
+    /// Report every file as its own group with its own summary and results file
+    parallel_files: bool,
+    urls: Vec<String>,
+    /// Where each URL came from, for error messages
+    sources: Vec<String>,
+    min_tls: &'static str,
+    summary_file: Option<PathBuf>,
+    timestamps: TimestampFormat,
+    /// Unit of response times and other durations in results and summary files
+    time_unit: TimeUnit,
+    color_theme: ColorTheme,
+    /// Whether the console is a terminal that takes colors
+    colors: bool,
+    output_format: OutputFormat,
+    /// Results file, `-` for stdout; defaults to `status.<format>`
+    output: Option<PathBuf>,
+    /// Tags every NDJSON line with the run that produced it
+    run_id: String,
+    csv: CsvWriter,
+    /// Namespace prepended to Graphite metric paths
+    graphite_prefix: Option<String>,
+    /// Carbon server Graphite lines are sent to instead of the results file
+    graphite_host: Option<String>,
+    /// Add line-based results to the end of the results file instead of
+    /// replacing it
+    append: bool,
+    /// Pass the run when at least this many URLs are healthy, however many
+    /// failed
+    require_up: Option<usize>,
+    /// Fail the run when a certificate expires in fewer days than this
+    fail_cert_within: Option<i64>,
+    /// Content lengths are read from and saved back to this file
+    size_state: Option<PathBuf>,
+    /// Split the results file into parts of at most this many results
+    max_results_per_file: Option<usize>,
+    /// Rotate the results file before a write once it is this many bytes
+    output_rotate_size: Option<u64>,
+    /// Rotated results files kept
+    output_rotate_count: u32,
+    /// Compression level of the results file, uncompressed when unset
+    output_gzip: Option<u32>,
+    validate_only: bool,
+    /// Print and write only the aggregate summary, no per-URL output
+    summary_only: bool,
+    /// Read JSON results and summary files back after writing them
+    verify_output: bool,
+    html_template: Option<String>,
+    /// Custom report rendered after every round, and where it goes
+    report_template: Option<(ReportTemplate, PathBuf)>,
+    checkpoint_every: Option<Duration>,
+    /// Repeat the scan with this period instead of running once
+    interval: Option<Duration>,
+    /// Check each URL with its own annotated period, `interval` by default
+    per_url_intervals: bool,
+    /// Stop after this many rounds in interval mode
+    rounds: Option<u64>,
+    /// Write every round as a time series instead of only the latest one
+    series: bool,
+    connection_stats: bool,
+    /// Sustained load mode: how long to run and the requests per second
+    load: Option<(Duration, f64)>,
+    /// Directory that gets one results file per worker thread
+    per_worker_output: Option<PathBuf>,
+    /// Notified in interval mode whenever a failing URL passes again
+    recovery_webhook: Option<String>,
+    /// Pushgateway that gets the metrics of every round
+    pushgateway: Option<String>,
+    job: String,
+    /// Fail the round when pushing its metrics fails
+    require_push: bool,
+    /// Exit codes by failure category instead of 1 for every failure
+    exit_code_map: Option<ExitCodeMap>,
+    /// Metrics for every result are sent here as they arrive
+    statsd: Option<StatsdClient>,
+    pid_file: Option<PathBuf>,
+    /// Raise the soft open file limit to this at startup, `u64::MAX` for
+    /// the hard limit
+    max_file_descriptors: Option<u64>,
+    /// Results file whose failed URLs are checked again
+    recheck: Option<PathBuf>,
+    /// Check only the URLs of this shard
+    shard: Option<Shard>,
+    /// Results files to combine instead of checking anything
+    merge_files: Vec<PathBuf>,
+    merge_strategy: MergeStrategy,
+    /// Indentation of JSON files when not the default four spaces
+    indent: Option<String>,
+    /// Front-end options as given, printed with the config
+    settings: Settings,
+    /// Print the resolved `config` instead of checking anything
+    print_config: bool,
+    /// Print it as JSON rather than TOML
+    print_config_json: bool,
+}
+
+impl Cli {
+    /// Check period of the URL at `index` in `urls`
+    fn url_interval(&self, index: usize) -> Option<Duration> {
+        let annotated = index.checked_sub(self.argument_count).and_then(|i| self.file_intervals.get(i).copied().flatten());
+        annotated.or(self.interval)
+    }
+
+    fn output_to_stdout(&self) -> bool {
+        self.output.as_deref() == Some(Path::new("-"))
+    }
+
+    fn output_path(&self) -> PathBuf {
+        self.output.clone().unwrap_or_else(|| match self.output_format {
+            OutputFormat::Json => PathBuf::from("status.json"),
+            OutputFormat::Logfmt => PathBuf::from("status.logfmt"),
+            OutputFormat::Ndjson => PathBuf::from("status.ndjson"),
+            OutputFormat::Graphite => PathBuf::from("status.graphite"),
+            OutputFormat::Csv => PathBuf::from("status.csv"),
+        })
+    }
+
+    /// `json` with the `--indent` indentation
+    fn indent_json(&self, json: String) -> String {
+        match &self.indent {
+            Some(indent) => output::reindent(&json, indent),
+            None => json,
+        }
+    }
+
+    /// A results file body for `results` in the chosen format
+    fn format_results(&self, results: &[WebsiteStatus]) -> String {
+        match self.output_format {
+            OutputFormat::Json => self.indent_json(output::json_array(results, self.timestamps, self.time_unit)),
+            OutputFormat::Logfmt => output::logfmt_lines(results, self.timestamps, self.time_unit),
+            OutputFormat::Ndjson => output::ndjson_lines(results, self.timestamps, self.time_unit, &self.run_id),
+            OutputFormat::Graphite => results.iter()
+                .map(|result| output::graphite_lines(result, self.graphite_prefix.as_deref()))
+                .collect(),
+            OutputFormat::Csv => output::csv_table(results, self.timestamps, self.time_unit, self.csv),
+        }
+    }
+
+    /// Write a results file, appending with `--append`
+    fn write_file(&self, path: &Path, contents: &str) -> std::io::Result<PathBuf> {
+        if let Some(limit) = self.output_rotate_size {
+            let target = if self.append { path.to_path_buf() } else { output::final_path(path, self.output_gzip) };
+            if output::rotate(&target, limit, self.output_rotate_count)? {
+                eprintln!("Rotated {} after it reached {} bytes", target.display(), limit);
+            }
+        }
+        if self.append {
+            output::append(path, contents.as_bytes())
+        } else {
+            output::write_atomic(path, self.output_gzip, contents.as_bytes())
+        }
+    }
+}
+
+/// Print a console message; it goes to stderr while stdout carries the
+/// results themselves (`--output -`)
+macro_rules! console {
+    ($cli:expr, $($arg:tt)*) => {
+        if $cli.output_to_stdout() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Parse command line arguments and load the URL file and report template
+fn parse_args() -> Cli {
+    let mut args = env::args().skip(1).peekable();
+    let mut print_config = false;
+    let mut print_config_json = false;
+    let check_version = args.next_if(|arg| arg == "check-version").is_some();
+    let mut file_paths = Vec::new();
+    let mut urls = Vec::new();
+    let (mut config, mut settings) = load_config_file().unwrap_or_default();
+    let mut validate_only = false;
+    let mut merge = false;
+    let mut merge_strategy = MergeStrategy::All;
+    let mut recheck: Option<PathBuf> = None;
+    let mut stdin_json = false;
+    let mut spec_schema = None;
+    let mut simulate_mobile = false;
+    let mut title = None;
+    let mut title_regex = false;
+    let mut title_case_sensitive = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // The subcommand and the option are the same, the option reads
+            // better after other options
+            "print-config" | "--print-config" => {
+                print_config = true;
+            }
+            "--print-config-format" => {
+                print_config_json = match args.next().as_deref() {
+                    Some("toml") => false,
+                    Some("json") => true,
+                    _ => {
+                        eprintln!("Invalid config format, expected toml or json");
+                        print_usage();
+                    }
+                };
+            }
+            "--config" => {
+                // Already applied by load_config_file
+                args.next();
+            }
+            "--file" => {
+                file_paths.push(args.next().map(PathBuf::from).unwrap_or_else(|| print_usage()));
+            }
+            "--parallel-files" => {
+                settings.parallel_files = true;
+            }
+            "--workers" => {
+                config.workers = args.next()
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or_else(|| {
+                        eprintln!("Invalid worker count, using default");
+                        Config::default().workers
+                    });
+            }
+            "--max-workers" => {
+                match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) if n > 0 => config.max_workers = Some(n),
+                    _ => {
+                        eprintln!("Invalid worker count");
+                        print_usage();
+                    }
+                }
+            }
+            "--per-ip-limit" => {
+                match args.next().and_then(|n| n.parse().ok()) {
+                    Some(limit) if limit > 0 => config.per_ip_limit = Some(limit),
+                    _ => {
+                        eprintln!("Invalid per-IP limit");
+                        print_usage();
+                    }
+                }
+            }
+            "--source-ip" => {
+                config.source_ip = args.next().and_then(|ip| ip.parse().ok());
+                if config.source_ip.is_none() {
+                    eprintln!("Invalid source IP address");
+                    print_usage();
+                }
+            }
+            "--ordered" => {
+                config.ordered_output = true;
+            }
+            "--timeout" => {
+                config.timeout = Duration::from_secs(
+                    args.next()
+                        .and_then(|t| t.parse().ok())
+                        .unwrap_or(5)
+                );
+            }
+            "--time-limit-per-domain" => {
+                let limit = args.next().and_then(|arg| {
+                    let (domain, secs) = arg.split_once('=')?;
+                    let secs: u64 = secs.parse().ok().filter(|secs| *secs > 0)?;
+                    let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+                    (!domain.is_empty()).then_some((domain, secs))
+                });
+                match limit {
+                    Some((domain, secs)) => {
+                        config.domain_timeouts.insert(domain, secs);
+                    }
+                    None => {
+                        eprintln!("Invalid per-domain time limit, expected DOMAIN=SECONDS");
+                        print_usage();
+                    }
+                }
+            }
+            "--retries" => {
+                config.retries = args.next()
+                    .and_then(|r| r.parse().ok())
+                    .unwrap_or(0);
+            }
+            "--retry-rate" => {
+                match args.next().and_then(|r| r.parse::<f64>().ok()) {
+                    Some(rate) if rate > 0.0 && rate.is_finite() => config.retry_rate = Some(rate),
+                    _ => {
+                        eprintln!("Invalid retry rate, expected retries per second");
+                        print_usage();
+                    }
+                }
+            }
+            "--method" => {
+                match args.next().filter(|m| Method::from_bytes(m.as_bytes()).is_ok()) {
+                    Some(method) => config.method = method.to_ascii_uppercase(),
+                    None => {
+                        eprintln!("Invalid HTTP method");
+                        print_usage();
+                    }
+                }
+            }
+            "--retry-non-idempotent" => {
+                config.retry_non_idempotent = true;
+            }
+            "--retry-on-http2-goaway" => {
+                config.retry_on_http2_goaway = true;
+            }
+            "--retry-on-error-matching" => {
+                match args.next().map(|pattern| Regex::new(&pattern)) {
+                    Some(Ok(pattern)) => config.retry_on_error = Some(Arc::new(pattern)),
+                    Some(Err(e)) => {
+                        eprintln!("Invalid regex: {}", e);
+                        print_usage();
+                    }
+                    None => print_usage(),
+                }
+            }
+            "--success-expr" => {
+                match args.next().map(|expr| SuccessExpr::parse(&expr)) {
+                    Some(Ok(expr)) => config.success_expr = Some(Arc::new(expr)),
+                    Some(Err(e)) => {
+                        eprintln!("Invalid success expression: {}", e);
+                        print_usage();
+                    }
+                    None => print_usage(),
+                }
+            }
+            "--stdin-json" => {
+                stdin_json = true;
+            }
+            "--validate-schema" => {
+                match args.next().map(|path| JsonSchema::load(&path)) {
+                    Some(Ok(schema)) => spec_schema = Some(schema),
+                    Some(Err(e)) => {
+                        eprintln!("Error reading JSON Schema {}", e);
+                        std::process::exit(1);
+                    }
+                    None => print_usage(),
+                }
+            }
+            "--check-schema-version" => {
+                match args.next().map(|path| JsonSchema::load(&path)) {
+                    Some(Ok(schema)) => config.schema = Some(Arc::new(schema)),
+                    Some(Err(e)) => {
+                        eprintln!("Error reading JSON Schema {}", e);
+                        std::process::exit(1);
+                    }
+                    None => print_usage(),
+                }
+            }
+            "--request-count" => {
+                match args.next().and_then(|n| n.parse().ok()) {
+                    Some(count) if count > 0 => config.request_count = count,
+                    _ => {
+                        eprintln!("Invalid request count");
+                        print_usage();
+                    }
+                }
+            }
+            "--retry-on-empty-body" => {
+                config.retry_on_empty_body = true;
+            }
+            "--disable-compression" => {
+                config.disable_compression = true;
+            }
+            "--check-response-not-empty" => {
+                config.check_response_not_empty = true;
+            }
+            "--min-response-bytes" => {
+                match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) if n > 0 => config.min_response_bytes = Some(n),
+                    _ => {
+                        eprintln!("Invalid byte count, expected a positive number");
+                        print_usage();
+                    }
+                }
+            }
+            "--ramp-up" => {
+                match args.next().and_then(|s| s.parse().ok()) {
+                    Some(secs) if secs > 0 => config.ramp_up = Some(Duration::from_secs(secs)),
+                    _ => {
+                        eprintln!("Invalid ramp-up time, expected seconds");
+                        print_usage();
+                    }
+                }
+            }
+            "--host-time-budget" => {
+                config.host_time_budget = args.next()
+                    .and_then(|s| s.parse().ok())
+                    .map(Duration::from_secs);
+            }
+            "--header" => {
+                match args.next().as_deref().and_then(|h| h.split_once(':')) {
+                    Some((name, value)) => {
+                        config.headers.push((name.trim().to_string(), value.trim().to_string()));
+                    }
+                    None => {
+                        eprintln!("Invalid header, expected 'NAME: VALUE'");
+                        print_usage();
+                    }
+                }
+            }
+            "--simulate-mobile" => {
+                simulate_mobile = true;
+            }
+            "--include-request-info" => {
+                config.include_request_info = true;
+            }
+            "--no-redact" => {
+                config.redact_headers = false;
+            }
+            "--detailed-timing" => {
+                config.detailed_timing = true;
+            }
+            "--check-ssl-expiry-days" => {
+                config.min_cert_days = args.next().and_then(|d| d.parse().ok());
+                if config.min_cert_days.is_none() {
+                    eprintln!("Invalid day count");
+                    print_usage();
+                }
+            }
+            "--fail-on-cert-expiry-within" => {
+                settings.fail_on_cert_expiry_within = args.next().and_then(|d| d.parse().ok());
+                if settings.fail_on_cert_expiry_within.is_none() {
+                    eprintln!("Invalid day count");
+                    print_usage();
+                }
+                config.cert_expiry_info = true;
+            }
+            "--tls-info" => {
+                config.tls_info = true;
+            }
+            "--min-tls" => {
+                settings.min_tls = args.next().unwrap_or_else(|| print_usage());
+                config.tls_info = true;
+            }
+            "--icmp-ping" => {
+                match args.next() {
+                    Some(host) => urls.push(format!("icmp://{}", host)),
+                    None => print_usage(),
+                }
+            }
+            "--smtp-check" => {
+                match args.next() {
+                    Some(addr) => urls.push(format!("smtp://{}", addr)),
+                    None => print_usage(),
+                }
+            }
+            "--smtp-ehlo" => {
+                config.smtp_ehlo = true;
+            }
+            "--ssh-check" => {
+                match args.next() {
+                    Some(addr) => urls.push(format!("ssh://{}", addr)),
+                    None => print_usage(),
+                }
+            }
+            "--expect-ssh-version" => {
+                config.min_ssh_version = args.next().and_then(|v| ssh::parse_version(&v));
+                if config.min_ssh_version.is_none() {
+                    eprintln!("Invalid SSH version, expected MAJOR.MINOR such as 8.9");
+                    print_usage();
+                }
+            }
+            "--dns-check" => {
+                match args.next() {
+                    Some(host) => urls.push(format!("dns://{}", host)),
+                    None => print_usage(),
+                }
+            }
+            "--dns-resolvers" => {
+                let resolvers: Option<Vec<_>> = args.next()
+                    .map(|list| list.split(',').map(|r| dns::parse_resolver(r.trim())).collect())
+                    .unwrap_or(None);
+                match resolvers {
+                    Some(resolvers) if !resolvers.is_empty() => config.dns_resolvers = resolvers,
+                    _ => {
+                        eprintln!("Invalid resolver list, expected IP[:PORT],IP[:PORT],...");
+                        print_usage();
+                    }
+                }
+            }
+            "--dns-server" => {
+                match args.next().and_then(|server| dns::parse_resolver(server.trim())) {
+                    Some(server) => config.dns_servers.push(server),
+                    None => {
+                        eprintln!("Invalid DNS server, expected IP[:PORT]");
+                        print_usage();
+                    }
+                }
+            }
+            "--verbose" | "-v" => {
+                config.verbose = true;
+            }
+            "--success-range" => {
+                match args.next().map(|r| parse_status_ranges(&r)) {
+                    Some(Ok(ranges)) => config.success_ranges.extend(ranges),
+                    Some(Err(e)) => {
+                        eprintln!("{}", e);
+                        print_usage();
+                    }
+                    None => print_usage(),
+                }
+            }
+            "--host-allowlist" | "--host-denylist" => {
+                let hosts: Vec<String> = args.next()
+                    .map(|list| list.split(',').map(|h| h.trim().to_string()).filter(|h| !h.is_empty()).collect())
+                    .unwrap_or_default();
+                if hosts.is_empty() {
+                    eprintln!("Invalid host list, expected HOST,HOST,...");
+                    print_usage();
+                }
+                if arg == "--host-allowlist" {
+                    config.host_allowlist.extend(hosts);
+                } else {
+                    config.host_denylist.extend(hosts);
+                }
+            }
+            "--check-link-count" | "--max-link-count" => {
+                let Some(count) = args.next().and_then(|n| n.parse().ok()) else {
+                    eprintln!("Invalid link count");
+                    print_usage();
+                };
+                if arg == "--check-link-count" {
+                    config.min_link_count = Some(count);
+                } else {
+                    config.max_link_count = Some(count);
+                }
+            }
+            "--check-title" => {
+                title = Some(args.next().unwrap_or_else(|| print_usage()));
+            }
+            "--check-title-regex" => {
+                title_regex = true;
+            }
+            "--check-title-case-sensitive" => {
+                title_case_sensitive = true;
+            }
+            "--save-bodies" => {
+                config.save_bodies = Some(args.next().map(PathBuf::from).unwrap_or_else(|| print_usage()));
+            }
+            "--capture-error-body" => {
+                config.capture_error_body = true;
+            }
+            "--max-body-size" => {
+                config.max_body_size = args.next()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or_else(|| print_usage());
+            }
+            "--body-store-format" => {
+                config.body_store_format = match args.next().as_deref() {
+                    Some("raw") => BodyStoreFormat::Raw,
+                    Some("with-headers") => BodyStoreFormat::WithHeaders,
+                    _ => {
+                        eprintln!("Invalid body store format, expected raw or with-headers");
+                        print_usage();
+                    }
+                };
+            }
+            "--normalize-trailing-slash" => {
+                config.check_trailing_slash = true;
+            }
+            "--detect-soft-404" => {
+                config.detect_soft_404 = true;
+            }
+            "--check-open-graph" => {
+                config.check_open_graph = true;
+            }
+            "--require-og-image-https" => {
+                config.check_open_graph = true;
+                config.require_og_image_https = true;
+            }
+            "--seed" => {
+                config.seed = match args.next().map(|s| s.parse()) {
+                    Some(Ok(seed)) => Some(seed),
+                    _ => {
+                        eprintln!("Invalid seed, expected a number");
+                        print_usage();
+                    }
+                };
+            }
+            "--preflight-head-then-get" => {
+                config.preflight_head = true;
+            }
+            "--check-image-alt" => {
+                config.check_image_alt = true;
+            }
+            "--require-image-alt" => {
+                config.check_image_alt = true;
+                config.require_image_alt = true;
+            }
+            "--check-sri" => {
+                config.check_sri = true;
+            }
+            "--require-sri" => {
+                config.check_sri = true;
+                config.require_sri = true;
+            }
+            "--check-viewport-meta" => {
+                config.check_viewport_meta = true;
+            }
+            "--require-viewport-initial-scale" => {
+                config.check_viewport_meta = true;
+                config.require_viewport_initial_scale = true;
+            }
+            "--check-structured-data" => {
+                config.check_structured_data = true;
+            }
+            "--require-structured-data-type" => {
+                config.check_structured_data = true;
+                config.required_structured_data_types.push(args.next().unwrap_or_else(|| print_usage()));
+            }
+            "--check-canonical" => {
+                config.check_canonical = true;
+            }
+            "--abort-on-cert-error-for-host" => {
+                config.abort_on_cert_error = true;
+            }
+            "--check-hpkp" => {
+                config.check_expect_ct = true;
+            }
+            "--require-expect-ct-enforce" => {
+                config.check_expect_ct = true;
+                config.require_expect_ct_enforce = true;
+            }
+            "--check-cache-headers" => {
+                config.check_cache_headers = true;
+            }
+            "--require-cacheable" => {
+                config.check_cache_headers = true;
+                config.require_cacheable = true;
+            }
+            "--max-age" => {
+                config.max_content_age = Some(args.next()
+                    .and_then(|s| s.parse().ok())
+                    .map(Duration::from_secs)
+                    .unwrap_or_else(|| {
+                        eprintln!("Invalid age, expected seconds");
+                        print_usage();
+                    }));
+            }
+            "--check-x-frame-options" => {
+                config.check_x_frame_options = true;
+            }
+            "--require-x-frame-options" => {
+                config.check_x_frame_options = true;
+                config.require_x_frame_options = true;
+            }
+            "--report-cookie-security" => {
+                config.report_cookie_security = true;
+            }
+            "--require-cookie-secure" => {
+                config.report_cookie_security = true;
+                config.require_cookie_secure = true;
+            }
+            "--version-header" => {
+                config.version_header = Some(args.next().unwrap_or_else(|| print_usage()));
+            }
+            "--expected-version" => {
+                match args.next().map(|req| VersionReq::parse(&req)) {
+                    Some(Ok(requirement)) => config.version_requirement = Some(requirement),
+                    Some(Err(e)) => {
+                        eprintln!("Invalid version requirement: {}", e);
+                        print_usage();
+                    }
+                    None => print_usage(),
+                }
+            }
+            "--track-size" => {
+                settings.track_size = args.next().map(PathBuf::from);
+            }
+            "--min-response-time" => {
+                config.min_response_time_ms = Some(args.next()
+                    .and_then(|ms| ms.parse().ok())
+                    .unwrap_or_else(|| print_usage()));
+            }
+            "--fail-too-fast" => {
+                config.fail_too_fast = true;
+            }
+            "--size-change-threshold" => {
+                match args.next().and_then(|p| p.parse().ok()) {
+                    Some(percent) if percent >= 0.0 => config.size_change_threshold = percent,
+                    _ => {
+                        eprintln!("Invalid size change threshold");
+                        print_usage();
+                    }
+                }
+            }
+            "--probe-paths" => {
+                config.probe_paths = args.next()
+                    .map(|list| list.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect())
+                    .unwrap_or_default();
+                if config.probe_paths.is_empty() {
+                    eprintln!("Invalid probe paths, expected /PATH,/PATH,...");
+                    print_usage();
+                }
+            }
+            "--probe-mode" => {
+                match args.next().as_deref() {
+                    Some("all") => config.probe_any = false,
+                    Some("any") => config.probe_any = true,
+                    _ => {
+                        eprintln!("Invalid probe mode, expected all or any");
+                        print_usage();
+                    }
+                }
+            }
+            "--validate" => {
+                validate_only = true;
+            }
+            "--merge" => {
+                merge = true;
+            }
+            "--recheck" => {
+                recheck = args.next().map(PathBuf::from);
+                if recheck.is_none() {
+                    print_usage();
+                }
+            }
+            "--merge-strategy" => {
+                merge_strategy = match args.next().as_deref() {
+                    Some("all") => MergeStrategy::All,
+                    Some("latest") => MergeStrategy::Latest,
+                    _ => {
+                        eprintln!("Invalid merge strategy, expected all or latest");
+                        print_usage();
+                    }
+                };
+            }
+            "--indent" => {
+                settings.indent = Some(args.next().unwrap_or_else(|| print_usage()));
+            }
+            "--shard" => {
+                settings.shard = Some(args.next().unwrap_or_else(|| print_usage()));
+            }
+            "--interval" => {
+                settings.interval = args.next().and_then(|s| s.parse().ok());
+                if settings.interval.is_none() {
+                    eprintln!("Invalid interval");
+                    print_usage();
+                }
+            }
+            "--probe-interval-per-url" => {
+                settings.probe_interval_per_url = true;
+            }
+            "--rounds" => {
+                settings.rounds = args.next().and_then(|n| n.parse().ok());
+                if settings.rounds.is_none() {
+                    eprintln!("Invalid round count");
+                    print_usage();
+                }
+            }
+            "--series" => {
+                settings.series = true;
+            }
+            "--connection-reuse-stats" => {
+                settings.connection_reuse_stats = true;
+            }
+            "--write-pid" => {
+                settings.write_pid = args.next().map(PathBuf::from);
+            }
+            "--max-file-descriptors" => {
+                settings.max_file_descriptors = Some(args.next().unwrap_or_else(|| print_usage()));
+            }
+            "--checkpoint-every" => {
+                settings.checkpoint_every = args.next().and_then(|s| s.parse().ok());
+            }
+            "--timestamp-format" => {
+                settings.timestamp_format = args.next().unwrap_or_else(|| print_usage());
+            }
+            "--time-unit" => {
+                settings.time_unit = args.next().unwrap_or_else(|| print_usage());
+            }
+            "--color-theme" => {
+                settings.color_theme = args.next().unwrap_or_else(|| print_usage());
+            }
+            "--timezone" => {
+                settings.timezone = args.next().unwrap_or_else(|| print_usage());
+            }
+            "--max-results-per-file" => {
+                match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) if n > 0 => settings.max_results_per_file = Some(n),
+                    _ => {
+                        eprintln!("Invalid result count");
+                        print_usage();
+                    }
+                }
+            }
+            "--output-format" => {
+                settings.output_format = args.next().unwrap_or_else(|| print_usage());
+            }
+            "--output" | "-o" => {
+                settings.output = Some(args.next().map(PathBuf::from).unwrap_or_else(|| print_usage()));
+            }
+            "--per-worker-output" => {
+                settings.per_worker_output = Some(args.next().map(PathBuf::from).unwrap_or_else(|| print_usage()));
+            }
+            "--load-duration" => {
+                match args.next().and_then(|s| s.parse().ok()) {
+                    Some(secs) if secs > 0 => settings.load_duration = Some(secs),
+                    _ => {
+                        eprintln!("Invalid load duration, expected seconds");
+                        print_usage();
+                    }
+                }
+            }
+            "--target-rps" => {
+                match args.next().and_then(|r| r.parse::<f64>().ok()) {
+                    Some(rps) if rps > 0.0 && rps.is_finite() => settings.target_rps = Some(rps),
+                    _ => {
+                        eprintln!("Invalid target rate, expected requests per second");
+                        print_usage();
+                    }
+                }
+            }
+            "--recovery-webhook" => {
+                settings.recovery_webhook = Some(args.next().unwrap_or_else(|| print_usage()));
+            }
+            "--pushgateway" => {
+                settings.pushgateway = Some(args.next().unwrap_or_else(|| print_usage()));
+            }
+            "--job" => {
+                settings.job = args.next().filter(|job| !job.is_empty()).unwrap_or_else(|| print_usage());
+            }
+            "--require-push" => {
+                settings.require_push = true;
+            }
+            "--exit-code-map" => {
+                settings.exit_code_map = Some(args.next().unwrap_or_else(|| print_usage()));
+            }
+            "--statsd" => {
+                settings.statsd = Some(args.next().unwrap_or_else(|| print_usage()));
+            }
+            "--run-id" => {
+                settings.run_id = Some(args.next().unwrap_or_else(|| print_usage()));
+            }
+            "--csv-delimiter" => {
+                // `\t` is accepted since a literal tab is awkward to pass
+                settings.csv_delimiter = args.next()
+                    .map(|d| if d == "\\t" { "\t".to_string() } else { d })
+                    .unwrap_or_else(|| print_usage());
+            }
+            "--csv-always-quote" => {
+                settings.csv_always_quote = true;
+            }
+            "--graphite-prefix" => {
+                settings.graphite_prefix = Some(args.next().unwrap_or_else(|| print_usage()));
+            }
+            "--graphite-host" => {
+                settings.graphite_host = Some(args.next().unwrap_or_else(|| print_usage()));
+            }
+            "--append" => {
+                settings.append = true;
+            }
+            "--require-up" => {
+                settings.require_up = match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) if n > 0 => Some(n),
+                    _ => {
+                        eprintln!("Invalid --require-up, expected a positive number of URLs");
+                        print_usage();
+                    }
+                };
+            }
+            "--output-rotate-size" => {
+                settings.output_rotate_size = match args.next().and_then(|bytes| bytes.parse().ok()) {
+                    Some(bytes) if bytes > 0 => Some(bytes),
+                    _ => {
+                        eprintln!("Invalid rotation size, expected bytes");
+                        print_usage();
+                    }
+                };
+            }
+            "--output-rotate-count" => {
+                settings.output_rotate_count = args.next()
+                    .and_then(|count| count.parse().ok())
+                    .unwrap_or_else(|| print_usage());
+            }
+            "--output-gzip" => {
+                settings.output_gzip = true;
+            }
+            "--gzip-level" => {
+                match args.next().and_then(|l| l.parse().ok()) {
+                    Some(level) if level <= 9 => settings.gzip_level = level,
+                    _ => {
+                        eprintln!("Invalid gzip level, expected 0-9");
+                        print_usage();
+                    }
+                }
+            }
+            "--html-template" => {
+                settings.html_template = args.next().map(PathBuf::from);
+            }
+            "--report-template" => {
+                settings.report_template = args.next().map(PathBuf::from);
+            }
+            "--report-out" => {
+                settings.report_out = args.next().map(PathBuf::from);
+            }
+            "--summary-only" => {
+                settings.summary_only = true;
+            }
+            "--verify-output" => {
+                settings.verify_output = true;
+            }
+            "--summary-file" => {
+                settings.summary_file = args.next().map(PathBuf::from);
+            }
+            arg if arg.starts_with("--") => {
+                eprintln!("Unknown option: {}", arg);
+                print_usage();
+            }
+            url => {
+                urls.push(url.to_string());
+            }
+        }
+    }
+
+    // The front-end settings of the config file are checked here together
+    // with the ones given as options
+    let parallel_files = settings.parallel_files;
+    let min_tls = tls::parse_version(&settings.min_tls).unwrap_or_else(|| {
+        eprintln!("Invalid TLS version, expected one of 1.0, 1.1, 1.2, 1.3");
+        print_usage();
+    });
+    let summary_file = settings.summary_file.clone();
+    let summary_only = settings.summary_only;
+    let output_format = match settings.output_format.as_str() {
+        "json" => OutputFormat::Json,
+        "logfmt" => OutputFormat::Logfmt,
+        "ndjson" => OutputFormat::Ndjson,
+        "graphite" => OutputFormat::Graphite,
+        "csv" => OutputFormat::Csv,
+        _ => {
+            eprintln!("Invalid output format, expected json, logfmt, ndjson, graphite or csv");
+            print_usage();
+        }
+    };
+    let output_file = settings.output.clone();
+    let append = settings.append;
+    let output_gzip = settings.output_gzip;
+    let gzip_level = settings.gzip_level;
+    let output_rotate_size = settings.output_rotate_size;
+    let output_rotate_count = settings.output_rotate_count;
+    let max_results_per_file = settings.max_results_per_file;
+    let per_worker_output = settings.per_worker_output.clone();
+    let verify_output = settings.verify_output;
+    let indent = match settings.indent.as_deref() {
+        None => None,
+        Some("tab") => Some("\t".to_string()),
+        Some(n) => match n.parse::<usize>() {
+            Ok(n) if n <= 8 => Some(" ".repeat(n)),
+            _ => {
+                eprintln!("Invalid indent, expected 0 to 8 spaces or tab");
+                print_usage();
+            }
+        },
+    };
+    let run_id = settings.run_id.clone();
+    let mut delimiter = settings.csv_delimiter.chars();
+    let csv = match (delimiter.next(), delimiter.next()) {
+        (Some(c), None) if c != '"' && c != '\n' && c != '\r' => CsvWriter { delimiter: c, always_quote: settings.csv_always_quote },
+        _ => {
+            eprintln!("Invalid CSV delimiter, expected a single character other than a quote or line break");
+            print_usage();
+        }
+    };
+    let graphite_prefix = settings.graphite_prefix.clone();
+    let graphite_host = settings.graphite_host.clone();
+    let timezone: Tz = settings.timezone.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid time zone, expected a name such as UTC or America/New_York");
+        print_usage();
+    });
+    let time_unit = TimeUnit::parse(&settings.time_unit).unwrap_or_else(|| {
+        eprintln!("Invalid time unit, expected ms, us or s");
+        print_usage();
+    });
+    let color_theme = ColorTheme::parse(&settings.color_theme).unwrap_or_else(|| {
+        eprintln!("Invalid color theme, expected default, colorblind or mono");
+        print_usage();
+    });
+    let html_template = settings.html_template.clone();
+    let report_template = settings.report_template.clone();
+    let report_out = settings.report_out.clone();
+    let checkpoint_every = settings.checkpoint_every.map(Duration::from_secs);
+    let interval = settings.interval.map(Duration::from_secs);
+    let per_url_intervals = settings.probe_interval_per_url;
+    let rounds = settings.rounds;
+    let series = settings.series;
+    let connection_stats = settings.connection_reuse_stats;
+    let require_up = settings.require_up;
+    let fail_cert_within = settings.fail_on_cert_expiry_within;
+    let size_state = settings.track_size.clone();
+    let shard = settings.shard.as_deref().map(|shard| {
+        Shard::parse(shard).unwrap_or_else(|e| {
+            eprintln!("Invalid shard: {}", e);
+            print_usage();
+        })
+    });
+    let load_duration = settings.load_duration.map(Duration::from_secs);
+    let target_rps = settings.target_rps;
+    let recovery_webhook = settings.recovery_webhook.clone();
+    let pushgateway = settings.pushgateway.clone();
+    let job = settings.job.clone();
+    let require_push = settings.require_push;
+    let exit_code_map = settings.exit_code_map.as_deref().map(|spec| {
+        ExitCodeMap::parse(spec).unwrap_or_else(|e| {
+            eprintln!("Invalid exit code map: {}", e);
+            print_usage();
+        })
+    });
+    let statsd = settings.statsd.clone();
+    let pid_file = settings.write_pid.clone();
+    let max_file_descriptors = settings.max_file_descriptors.as_deref().map(|limit| match limit {
+        "max" => u64::MAX,
+        n => n.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid file descriptor limit, expected a number or max");
+            print_usage();
+        }),
+    });
+
+    // Read URLs from file if specified, remembering where each came from
+    let mut sources: Vec<String> = (1..=urls.len())
+        .map(|n| format!("argument {}", n))
+        .collect();
+    if let Some(path) = &recheck {
+        if !urls.is_empty() || !file_paths.is_empty() || merge {
+            eprintln!("--recheck takes its URLs from the results file and cannot be combined with URLs, --file or --merge");
+            print_usage();
+        }
+        urls = read_failed_urls(path);
+        sources = vec![path.display().to_string(); urls.len()];
+    }
+    if spec_schema.is_some() && !stdin_json {
+        eprintln!("--validate-schema validates the specs read with --stdin-json");
+        print_usage();
+    }
+    if stdin_json {
+        if recheck.is_some() || merge {
+            eprintln!("--stdin-json cannot be combined with --recheck or --merge");
+            print_usage();
+        }
+        let mut input = String::new();
+        if let Err(e) = std::io::stdin().read_to_string(&mut input) {
+            eprintln!("Error reading URL specs from stdin: {}", e);
+            std::process::exit(1);
+        }
+        match targets::read_json_specs(&input, spec_schema.as_ref()) {
+            Ok(specs) => {
+                sources.extend((0..specs.len()).map(|index| format!("stdin spec {}", index)));
+                urls.extend(specs);
+            }
+            Err(errors) => {
+                for error in &errors {
+                    eprintln!("Invalid URL spec on stdin: {}", error);
+                }
+                std::process::exit(1);
+            }
+        }
+    }
+    let argument_count = urls.len();
+    let (file_groups, file_intervals) = match read_url_files(&file_paths).and_then(|files| {
+        check_bodies(&files, &config.method)?;
+        Ok(files)
+    }) {
+        Ok(files) => {
+            sources.extend(files.sources);
+            urls.extend(files.urls);
+            config.url_bodies = Arc::new(files.bodies);
+            config.url_retries = Arc::new(files.retries);
+            (files.groups, files.intervals)
+        }
+        Err(e) => {
+            eprintln!("Error reading file: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if parallel_files && output_file.as_deref() == Some(Path::new("-")) {
+        eprintln!("--parallel-files writes one results file per group and cannot write to stdout");
+        print_usage();
+    }
+
+    if check_version && (config.version_header.is_none() || config.version_requirement.is_none()) {
+        eprintln!("check-version needs --version-header and --expected-version");
+        print_usage();
+    }
+
+    if series && max_results_per_file.is_some() {
+        eprintln!("--series and --max-results-per-file cannot be combined");
+        print_usage();
+    }
+    if !matches!(output_format, OutputFormat::Json) && (series || max_results_per_file.is_some()) {
+        eprintln!("--series and --max-results-per-file need JSON output");
+        print_usage();
+    }
+    if output_file.as_deref() == Some(Path::new("-")) && output_rotate_size.is_some() {
+        eprintln!("--output-rotate-size needs a results file and cannot be combined with --output -");
+        print_usage();
+    }
+    if output_file.as_deref() == Some(Path::new("-")) && (output_gzip || max_results_per_file.is_some()) {
+        eprintln!("--output - cannot be combined with --output-gzip or --max-results-per-file");
+        print_usage();
+    }
+
+    let load = match (load_duration, target_rps) {
+        (Some(duration), Some(rps)) => Some((duration, rps)),
+        (None, None) => None,
+        _ => {
+            eprintln!("--load-duration and --target-rps must be given together");
+            print_usage();
+        }
+    };
+    if load.is_some() && interval.is_some() {
+        eprintln!("--load-duration cannot be combined with --interval");
+        print_usage();
+    }
+
+    if per_url_intervals && interval.is_none() {
+        eprintln!("--probe-interval-per-url needs --interval for URLs without their own");
+        print_usage();
+    }
+    if !per_url_intervals && file_intervals.iter().any(Option::is_some) {
+        eprintln!("Warning: interval= annotations are ignored without --probe-interval-per-url");
+    }
+
+    if config.fail_too_fast && config.min_response_time_ms.is_none() {
+        eprintln!("--fail-too-fast needs --min-response-time");
+        print_usage();
+    }
+    if let Some(max) = config.max_workers
+        && (max < config.workers || config.ramp_up.is_some())
+    {
+        eprintln!("--max-workers must be at least --workers and cannot be combined with --ramp-up");
+        print_usage();
+    }
+
+    if require_push && pushgateway.is_none() {
+        eprintln!("--require-push needs --pushgateway");
+        print_usage();
+    }
+    if recovery_webhook.is_some() && interval.is_none() {
+        eprintln!("--recovery-webhook needs --interval");
+        print_usage();
+    }
+    if run_id.is_some() && !matches!(output_format, OutputFormat::Ndjson) {
+        eprintln!("--run-id needs --output-format ndjson");
+        print_usage();
+    }
+    if (graphite_prefix.is_some() || graphite_host.is_some()) && !matches!(output_format, OutputFormat::Graphite) {
+        eprintln!("--graphite-prefix and --graphite-host need --output-format graphite");
+        print_usage();
+    }
+    if graphite_host.is_some() && (output_file.is_some() || append || parallel_files || checkpoint_every.is_some()) {
+        eprintln!("--graphite-host sends metrics instead of writing a results file and cannot be combined with --output, --append, --parallel-files or --checkpoint-every");
+        print_usage();
+    }
+    if append && (matches!(output_format, OutputFormat::Json | OutputFormat::Csv) || output_gzip || checkpoint_every.is_some()) {
+        eprintln!("--append needs logfmt, ndjson or graphite output and cannot be combined with --output-gzip or --checkpoint-every");
+        print_usage();
+    }
+
+    let timestamps = TimestampFormat::parse(&settings.timestamp_format, timezone).unwrap_or_else(|| {
+        eprintln!("Invalid timestamp format, expected unix or rfc3339");
+        print_usage();
+    });
+
+    // Check if we have any URLs to process
+    if urls.is_empty() && !print_config {
+        print_usage();
+    }
 
-#[derive(Debug, Clone)]
-struct WebsiteStatus {
-    url: String,
-    action_status: Result<u16, String>,
-    response_time: Duration,
-    timestamp: SystemTime,
-}
+    // In merge mode the arguments name results files rather than URLs
+    let merge_files: Vec<PathBuf> = if merge { urls[..argument_count].iter().map(PathBuf::from).collect() } else { Vec::new() };
+    if merge && (merge_files.is_empty() || !matches!(output_format, OutputFormat::Json)) {
+        eprintln!("--merge needs results files as arguments and writes JSON results");
+        print_usage();
+    }
 
-impl WebsiteStatus {
-    fn to_json_string(&self) -> String {
-        let status = match &self.action_status {
-            Ok(code) => code.to_string(),
-            Err(e) => format!("\"{}\"", e.replace('"', "\\\"")),
+    // A missing state file is a first run, anything unreadable is an error
+    if let Some(path) = &size_state {
+        let baseline = match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!("Error reading size state {}: {}", path.display(), e);
+                std::process::exit(1);
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                eprintln!("Error reading size state {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
         };
-        
-        let timestamp = self.timestamp
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap_or(Duration::from_secs(0))
-            .as_secs();
-            
-        format!(
-            r#"{{
-    "url": "{}",
-    "status": {},
-    "response_time_ms": {},
-    "timestamp": {}
-}}"#,
-            self.url.replace('"', "\\\""),
-            status,
-            self.response_time.as_millis(),
-            timestamp
-        )
+        config.size_baseline = Some(Arc::new(baseline));
+    }
+
+    // Substrings are matched as escaped regexes, case-insensitive unless asked
+    if let Some(title) = title {
+        let pattern = if title_regex { title } else { regex::escape(&title) };
+        let pattern = if title_case_sensitive { pattern } else { format!("(?i){}", pattern) };
+        match Regex::new(&pattern) {
+            Ok(regex) => config.title_pattern = Some(Arc::new(regex)),
+            Err(e) => {
+                eprintln!("Invalid title pattern: {}", e);
+                print_usage();
+            }
+        }
+    } else if title_regex || title_case_sensitive {
+        eprintln!("--check-title-regex and --check-title-case-sensitive need --check-title");
+        print_usage();
+    }
+
+    // Explicit --header values win over the simulated ones
+    if simulate_mobile {
+        for (name, value) in MOBILE_HEADERS {
+            if !config.headers.iter().any(|(existing, _)| existing.eq_ignore_ascii_case(name)) {
+                config.headers.push((name.to_string(), value.to_string()));
+            }
+        }
+    }
+
+    if let Some(dir) = &config.save_bodies
+        && let Err(e) = std::fs::create_dir_all(dir)
+    {
+        eprintln!("Error creating body directory {}: {}", dir.display(), e);
+        std::process::exit(1);
+    }
+
+    if let Some(dir) = &per_worker_output
+        && let Err(e) = std::fs::create_dir_all(dir)
+    {
+        eprintln!("Error creating per-worker output directory {}: {}", dir.display(), e);
+        std::process::exit(1);
+    }
+
+    let statsd = statsd.map(|server| {
+        StatsdClient::new(&server).unwrap_or_else(|e| {
+            eprintln!("Error setting up StatsD client for {}: {}", server, e);
+            std::process::exit(1);
+        })
+    });
+
+    // Load the report template up front so a bad path fails before the scan
+    let html_template = html_template.map(|path| {
+        std::fs::read_to_string(&path).unwrap_or_else(|e| {
+            eprintln!("Error reading HTML template: {}", e);
+            std::process::exit(1);
+        })
+    });
+    let report_template = match (report_template, report_out) {
+        (Some(path), Some(out)) => {
+            let template = ReportTemplate::load(&path).unwrap_or_else(|e| {
+                eprintln!("Error reading report template: {}", e);
+                std::process::exit(1);
+            });
+            Some((template, out))
+        }
+        (None, None) => None,
+        _ => {
+            eprintln!("--report-template and --report-out need each other");
+            print_usage();
+        }
+    };
+
+    let run_id = run_id.unwrap_or_else(|| output::generate_run_id(&Rng::new(config.seed)));
+    // The console is stderr when results go to stdout
+    let colors = theme::colors_enabled(if output_file.as_deref() == Some(Path::new("-")) {
+        std::io::stderr().is_terminal()
+    } else {
+        std::io::stdout().is_terminal()
+    });
+    let mut cli = Cli {
+        config,
+        file_paths,
+        argument_count,
+        file_groups,
+        file_intervals,
+        parallel_files,
+        urls,
+        sources,
+        min_tls,
+        summary_file,
+        timestamps,
+        time_unit,
+        color_theme,
+        colors,
+        output_format,
+        output: output_file,
+        run_id,
+        csv,
+        graphite_prefix,
+        graphite_host,
+        append,
+        fail_cert_within,
+        require_up,
+        size_state,
+        max_results_per_file,
+        output_gzip: output_gzip.then_some(gzip_level),
+        output_rotate_size,
+        output_rotate_count,
+        validate_only,
+        merge_files,
+        merge_strategy,
+        shard,
+        recheck,
+        summary_only,
+        verify_output,
+        html_template,
+        report_template,
+        checkpoint_every,
+        interval,
+        per_url_intervals,
+        rounds,
+        series,
+        connection_stats,
+        load,
+        per_worker_output,
+        recovery_webhook,
+        pushgateway,
+        job,
+        require_push,
+        exit_code_map,
+        statsd,
+        pid_file,
+        max_file_descriptors,
+        indent,
+        settings,
+        print_config,
+        print_config_json,
+    };
+    if !merge {
+        apply_shard(&mut cli);
     }
+    cli
 }
 
-/// Print usage instructions and exit
-fn print_usage() -> ! {
-    eprintln!("Usage: website_checker [--file sites.txt] [URL ...]");
-    eprintln!("       [--workers N] [--timeout S] [--retries N]");
-    std::process::exit(2);
+/// The URLs that failed in a `--recheck` results file, exiting when there
+/// are none or the file cannot be read
+fn read_failed_urls(path: &Path) -> Vec<String> {
+    // Only a URL's latest result counts, an earlier failure may be fixed
+    let previous = merge::merge(&[path.to_path_buf()], MergeStrategy::Latest).unwrap_or_else(|e| {
+        eprintln!("Error reading results: {}", e);
+        std::process::exit(1);
+    });
+    for warning in &previous.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    if previous.summary.failed.is_empty() {
+        eprintln!("No failed URLs in {}, nothing to recheck", path.display());
+        std::process::exit(0);
+    }
+    eprintln!(
+        "Rechecking {} of {} URLs that failed in {}",
+        previous.summary.failed.len(),
+        previous.summary.total,
+        path.display()
+    );
+    previous.summary.failed
 }
 
-fn main() {
-    // Parse command line arguments
-    let mut args = env::args().skip(1);
-    let mut file_path = None;
-    let mut urls = Vec::new();
-    let mut workers = std::thread::available_parallelism()
-        .map(|n| n.get())
-        .unwrap_or(1);
-    let mut timeout = 5;
-    let mut retries = 0;
+/// Drop the URLs outside `--shard`, keeping the lists that run alongside
+/// `urls` in step
+fn apply_shard(cli: &mut Cli) {
+    let Some(shard) = cli.shard else {
+        return;
+    };
+    let keep: Vec<bool> = cli.urls.iter().map(|url| shard.contains(url)).collect();
+    let mut kept = keep.iter().copied();
+    cli.urls.retain(|_| kept.next().unwrap_or(false));
+    let mut kept = keep.iter().copied();
+    cli.sources.retain(|_| kept.next().unwrap_or(false));
+    let (arguments, files) = keep.split_at(cli.argument_count.min(keep.len()));
+    cli.argument_count = arguments.iter().filter(|keep| **keep).count();
+    let mut kept = files.iter().copied();
+    cli.file_groups.retain(|_| kept.next().unwrap_or(false));
+    let mut kept = files.iter().copied();
+    cli.file_intervals.retain(|_| kept.next().unwrap_or(false));
+    if cli.urls.is_empty() {
+        eprintln!("Warning: shard {}/{} has none of the URLs", shard.index, shard.total);
+    }
+}
 
-    while let Some(arg) = args.next() {
-        match arg.as_str() {
-            "--file" => {
-                file_path = args.next().map(PathBuf::from);
+/// Read the file named by `--config`, if any, with its `[cli]` table. It is
+/// loaded before the other options are parsed so that they override it
+/// wherever they appear
+fn load_config_file() -> Option<(Config, Settings)> {
+    let mut args = env::args().skip_while(|arg| arg != "--config").skip(1);
+    let path = args.next()?;
+    let loaded = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| {
+            let file: ConfigFile = toml::from_str(&contents).map_err(|e| e.to_string())?;
+            Ok((Config::from_toml(&contents)?, file.cli))
+        });
+    match loaded {
+        Ok(loaded) => Some(loaded),
+        Err(e) => {
+            eprintln!("Error reading config file {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// URLs read from the `--file` lists
+#[derive(Default)]
+struct UrlFiles {
+    urls: Vec<String>,
+    /// `file:line` of each URL
+    sources: Vec<String>,
+    /// Index of the file each URL came from
+    groups: Vec<usize>,
+    /// Check period annotated on each URL
+    intervals: Vec<Option<Duration>>,
+    /// Request body annotated on URLs
+    bodies: HashMap<String, Vec<u8>>,
+    /// Retry count annotated on URLs
+    retries: HashMap<String, u32>,
+}
+
+fn read_url_files(paths: &[PathBuf]) -> Result<UrlFiles, String> {
+    let mut files = UrlFiles::default();
+    for (group, path) in paths.iter().enumerate() {
+        let entries = targets::read_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        for entry in entries {
+            // Bodies are looked up by URL, one URL cannot have two
+            if let Some(body) = entry.body {
+                if files.bodies.get(&entry.url).is_some_and(|existing| *existing != body) {
+                    return Err(format!("{}:{}: {} already has a different body", path.display(), entry.line, entry.url));
+                }
+                files.bodies.insert(entry.url.clone(), body);
             }
-            "--workers" => {
-                workers = args.next()
-                    .and_then(|n| n.parse().ok())
-                    .unwrap_or_else(|| {
-                        eprintln!("Invalid worker count, using default");
-                        std::thread::available_parallelism()
-                            .map(|n| n.get())
-                            .unwrap_or(1)
-                    });
+            if let Some(retries) = entry.retries {
+                if files.retries.get(&entry.url).is_some_and(|existing| *existing != retries) {
+                    return Err(format!("{}:{}: {} already has a different retry count", path.display(), entry.line, entry.url));
+                }
+                files.retries.insert(entry.url.clone(), retries);
             }
-            "--timeout" => {
-                timeout = args.next()
-                    .and_then(|t| t.parse().ok())
-                    .unwrap_or(5);
+            files.sources.push(format!("{}:{}", path.display(), entry.line));
+            files.urls.push(entry.url);
+            files.groups.push(group);
+            files.intervals.push(entry.interval);
+        }
+    }
+    Ok(files)
+}
+
+/// Check that the request method can carry the `body=` annotations
+fn check_bodies(files: &UrlFiles, method: &str) -> Result<(), String> {
+    match files.bodies.keys().next() {
+        Some(url) if !matches!(method, "POST" | "PUT" | "PATCH") => Err(format!(
+            "{} has a body, which needs --method POST, PUT or PATCH rather than {}",
+            url, method
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Re-read the URL files so the next round checks their current contents,
+/// keeping URLs given on the command line
+fn reload_url_files(cli: &mut Cli) {
+    if cli.file_paths.is_empty() {
+        return;
+    }
+    let files = match read_url_files(&cli.file_paths).and_then(|files| {
+        check_bodies(&files, &cli.config.method)?;
+        Ok(files)
+    }) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("Error reloading {}, keeping the current URL list", e);
+            return;
+        }
+    };
+
+    let mut urls = cli.urls[..cli.argument_count].to_vec();
+    urls.extend(files.urls);
+    let previous = std::mem::replace(&mut cli.urls, urls);
+    cli.sources.truncate(cli.argument_count);
+    cli.sources.extend(files.sources);
+    cli.file_groups = files.groups;
+    cli.file_intervals = files.intervals;
+    cli.config.url_bodies = Arc::new(files.bodies);
+    cli.config.url_retries = Arc::new(files.retries);
+    apply_shard(cli);
+
+    let added = cli.urls.iter().filter(|url| !previous.contains(url)).count();
+    let removed = previous.iter().filter(|url| !cli.urls.contains(url)).count();
+    eprintln!(
+        "Reloaded {}: {} URLs added, {} removed, {} total",
+        cli.file_paths.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", "),
+        added,
+        removed,
+        cli.urls.len()
+    );
+}
+
+/// Split results into one group per `--file`, named after the file, plus an
+/// `arguments` group for URLs given on the command line. Each group keeps
+/// input order
+fn group_results(cli: &Cli, results: &[WebsiteStatus]) -> Vec<(String, Vec<WebsiteStatus>)> {
+    let mut names: Vec<String> = Vec::new();
+    for path in &cli.file_paths {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let mut name = stem.clone();
+        let mut n = 1;
+        while names.contains(&name) {
+            n += 1;
+            name = format!("{}-{}", stem, n);
+        }
+        names.push(name);
+    }
+    let mut groups: Vec<_> = names.into_iter().map(|name| (name, Vec::new())).collect();
+    if cli.argument_count > 0 {
+        groups.push(("arguments".to_string(), Vec::new()));
+    }
+
+    // The same URL may appear more than once, hand out its results in turn
+    let mut by_url: HashMap<&str, Vec<&WebsiteStatus>> = HashMap::new();
+    for result in results.iter().rev() {
+        by_url.entry(result.url.as_str()).or_default().push(result);
+    }
+    let argument_group = cli.file_paths.len();
+    let url_groups = std::iter::repeat_n(argument_group, cli.argument_count).chain(cli.file_groups.iter().copied());
+    for (url, group) in cli.urls.iter().zip(url_groups) {
+        if let Some(result) = by_url.get_mut(url.as_str()).and_then(Vec::pop) {
+            groups[group].1.push(result.clone());
+        }
+    }
+    groups
+}
+
+/// Contents of the results file: the latest round, or every round so far
+/// followed by `current` in series mode
+fn results_json(cli: &Cli, history: &[Round], current: &Round) -> String {
+    cli.indent_json(if cli.series {
+        output::series_json(history.iter().chain(std::iter::once(current)), cli.timestamps, cli.time_unit)
+    } else {
+        output::json_array(&current.results, cli.timestamps, cli.time_unit)
+    })
+}
+
+/// Report URLs whose size changed beyond the threshold and save the new
+/// sizes, which become the baseline of the next round
+fn update_sizes(cli: &mut Cli, results: &[WebsiteStatus]) {
+    let (Some(path), Some(baseline)) = (&cli.size_state, &cli.config.size_baseline) else {
+        return;
+    };
+
+    for result in results.iter().filter(|r| r.size_changed) {
+        eprintln!(
+            "Size changed: {} by {:+} bytes, now {}",
+            result.url,
+            result.size_delta.unwrap_or_default(),
+            result.content_length.unwrap_or_default()
+        );
+    }
+
+    let mut sizes: BTreeMap<String, u64> = baseline.iter().map(|(url, size)| (url.clone(), *size)).collect();
+    for result in results {
+        if let Some(length) = result.content_length {
+            sizes.insert(result.url.clone(), length);
+        }
+    }
+    let state = serde_json::to_string_pretty(&sizes).unwrap_or_default();
+    if let Err(e) = output::write_atomic(path, None, state.as_bytes()) {
+        eprintln!("Failed to write size state: {}", e);
+    }
+    cli.config.size_baseline = Some(Arc::new(sizes.into_iter().collect()));
+}
+
+/// Write the results file, or its parts and manifest with
+/// `--max-results-per-file`. Returns the path written, `None` for stdout
+fn write_results(cli: &Cli, history: &[Round], current: &Round) -> std::io::Result<Option<PathBuf>> {
+    let contents = match cli.output_format {
+        // Streamed line by line as results arrive
+        OutputFormat::Logfmt | OutputFormat::Ndjson | OutputFormat::Graphite if cli.output_to_stdout() => {
+            return Ok(None);
+        }
+        OutputFormat::Graphite if let Some(server) = &cli.graphite_host => {
+            let contents = cli.format_results(&current.results);
+            output::send_graphite(server, contents.as_bytes(), cli.config.timeout)?;
+            console!(cli, "Metrics sent to {}", server);
+            return Ok(None);
+        }
+        OutputFormat::Logfmt | OutputFormat::Ndjson | OutputFormat::Graphite | OutputFormat::Csv => {
+            cli.format_results(&current.results)
+        }
+        OutputFormat::Json => {
+            if let Some(per_file) = cli.max_results_per_file {
+                let path = cli.output_path();
+                return output::write_parts(&path, cli.output_gzip, &current.results, per_file, cli.timestamps, cli.time_unit)
+                    .map(Some);
             }
-            "--retries" => {
-                retries = args.next()
-                    .and_then(|r| r.parse().ok())
-                    .unwrap_or(0);
+            results_json(cli, history, current)
+        }
+    };
+
+    if cli.output_to_stdout() {
+        println!("{}", contents.trim_end());
+        return Ok(None);
+    }
+    cli.write_file(&cli.output_path(), &contents).map(Some)
+}
+
+/// Read a JSON results file back with `--verify-output`, logging what is
+/// wrong with it. Other formats, and results split into parts, are skipped
+fn verify_results(cli: &Cli, path: &Path) {
+    let json = matches!(cli.output_format, OutputFormat::Json | OutputFormat::Ndjson);
+    if !cli.verify_output || !json || cli.max_results_per_file.is_some() {
+        return;
+    }
+    match output::verify_results(path, cli.time_unit) {
+        Ok(count) => console!(cli, "Verified {} results in {}", count, path.display()),
+        Err(e) => eprintln!("Output verification failed for {}: {}", path.display(), e),
+    }
+}
+
+/// Write `worker-N.json` into `dir` for every worker, holding only the
+/// results that worker produced. Workers added by `--max-workers` get the
+/// next numbers
+fn write_worker_results(cli: &Cli, dir: &Path, results: &[WebsiteStatus]) -> std::io::Result<()> {
+    let workers = results.iter().map(|r| r.worker + 1).max().unwrap_or(0).max(cli.config.workers);
+    for worker in 0..workers {
+        let own: Vec<_> = results.iter().filter(|r| r.worker == worker).cloned().collect();
+        let path = dir.join(format!("worker-{}.json", worker));
+        output::write_atomic(&path, None, cli.indent_json(output::json_array(&own, cli.timestamps, cli.time_unit)).as_bytes())?;
+    }
+    Ok(())
+}
+
+/// POST a recovery notice for every URL that failed in the previous round
+/// and succeeds now
+fn notify_recoveries(cli: &Cli, endpoint: &str, previous: &HashMap<String, WebsiteStatus>, results: &[WebsiteStatus]) {
+    for result in results.iter().filter(|r| r.success) {
+        let Some(before) = previous.get(&result.url).filter(|before| !before.success) else {
+            continue;
+        };
+        let payload = webhook::recovery_payload(before, result, cli.timestamps, cli.time_unit);
+        match webhook::post(endpoint, payload, cli.config.timeout) {
+            Ok(()) => console!(cli, "Recovered: {}, notified {}", result.url, endpoint),
+            Err(e) => eprintln!("Failed to send recovery webhook for {}: {}", result.url, e),
+        }
+    }
+}
+
+/// Check the given URLs once, printing results as they complete and rewriting
+/// the results file every checkpoint interval
+fn scan(cli: &Cli, supervisor: &Supervisor, history: &[Round], number: u64, urls: Vec<String>) -> Result<Round, String> {
+    let timestamp = SystemTime::now();
+    let mut checkpoint_results = Vec::new();
+    let mut last_checkpoint = Instant::now();
+    // Once the process runs out of file descriptors every further check
+    // fails the same way, so the error is explained once instead of per URL
+    let mut out_of_files = 0;
+
+    if let Some(ramp) = cli.config.ramp_up
+        && cli.config.workers > 1
+        && cli.config.verbose
+    {
+        console!(
+            cli,
+            "Ramping up from 1 to {} concurrent checks over {}s, one more every {}ms",
+            cli.config.workers,
+            ramp.as_secs(),
+            (ramp / (cli.config.workers as u32 - 1)).as_millis()
+        );
+    }
+
+    let results = website_project::run(urls, &cli.config, |status| {
+        supervisor.record(status);
+        if let Some(statsd) = &cli.statsd {
+            statsd.send(status);
+        }
+        let mut repeated_out_of_files = false;
+        if status.action_status.as_ref().is_err_and(|e| e == limits::TOO_MANY_OPEN_FILES) {
+            out_of_files += 1;
+            repeated_out_of_files = out_of_files > 1;
+            if !repeated_out_of_files {
+                eprintln!(
+                    "Error: ran out of open files checking {}; raise the limit with `ulimit -n` or --max-file-descriptors max, or lower --workers (now {})",
+                    status.url, cli.config.workers
+                );
             }
-            arg if arg.starts_with("--") => {
-                eprintln!("Unknown option: {}", arg);
-                print_usage();
+        }
+        if cli.summary_only {
+            return;
+        }
+        if cli.output_to_stdout() {
+            match cli.output_format {
+                OutputFormat::Logfmt => println!("{}", output::logfmt_line(status, cli.timestamps, cli.time_unit)),
+                OutputFormat::Ndjson => println!("{}", status.to_json_line(cli.timestamps, cli.time_unit, &cli.run_id)),
+                OutputFormat::Graphite => print!("{}", output::graphite_lines(status, cli.graphite_prefix.as_deref())),
+                OutputFormat::Json | OutputFormat::Csv => {}
             }
-            url => {
-                urls.push(url.to_string());
+        }
+        if !repeated_out_of_files {
+            console!(
+                cli,
+                "{} - {} in {}ms",
+                status.url,
+                cli.color_theme.paint(status.success, &status.describe(), cli.colors),
+                status.response_time.as_millis()
+            );
+        }
+
+        if let Some(interval) = cli.checkpoint_every
+            && !cli.output_to_stdout()
+        {
+            checkpoint_results.push(status.clone());
+            if last_checkpoint.elapsed() >= interval {
+                let round = Round { number, timestamp, results: checkpoint_results.clone() };
+                if let Err(e) = write_results(cli, history, &round) {
+                    eprintln!("Failed to write checkpoint: {}", e);
+                }
+                last_checkpoint = Instant::now();
             }
         }
+    })?;
+    if out_of_files > 1 {
+        console!(cli, "{} more checks failed with too many open files", out_of_files - 1);
     }
 
-    // Read URLs from file if specified
-    if let Some(file_path) = file_path {
-        match File::open(&file_path) {
-            Ok(file) => {
-                let reader = BufReader::new(file);
-                urls.extend(
-                    reader.lines()
-                        .filter_map(Result::ok)
-                        .map(|line| line.trim().to_string())
-                        .filter(|line| !line.is_empty() && !line.starts_with('#'))
-                );
+    Ok(Round { number, timestamp, results })
+}
+
+/// Print and write the summary, HTML report and TLS compliance for a round.
+/// Returns whether the round passed: every check succeeded and no
+/// certificate expires within `--fail-on-cert-expiry-within`
+fn report_round(cli: &Cli, results: &[WebsiteStatus], groups: &[(String, Vec<WebsiteStatus>)]) -> bool {
+    let summary = Summary::from_results(results);
+    console!(
+        cli,
+        "Checked {} URLs: {} succeeded, {} failed",
+        summary.total,
+        summary.succeeded,
+        summary.failed.len()
+    );
+    if cli.summary_only {
+        console!(
+            cli,
+            "Response times: p50 {}ms, p95 {}ms, p99 {}ms",
+            summary.p50.as_millis(),
+            summary.p95.as_millis(),
+            summary.p99.as_millis()
+        );
+        for url in &summary.failed {
+            console!(cli, "Failed: {}", url);
+        }
+    }
+    for result in results.iter().filter(|r| r.trailing_slash_mismatch) {
+        if let Some(variant) = &result.trailing_slash_variant {
+            let describe = |status: &Result<u16, String>| match status {
+                Ok(code) => format!("HTTP {}", code),
+                Err(e) => e.clone(),
+            };
+            console!(
+                cli,
+                "Trailing slash mismatch: {} gave {}, {} gave {}",
+                result.url,
+                describe(&result.action_status),
+                variant.path,
+                describe(&variant.action_status)
+            );
+        }
+    }
+
+    for result in results.iter().filter(|r| r.canonical_mismatch) {
+        if let Some(canonical) = &result.canonical_url {
+            console!(cli, "Canonical mismatch: {} declares {}", result.url, canonical);
+        }
+    }
+
+    // Every URL of a recheck failed before, say which of them are fixed
+    let mut recheck_section = None;
+    if let Some(path) = &cli.recheck {
+        let (fixed, still_failing): (Vec<&WebsiteStatus>, Vec<&WebsiteStatus>) = results.iter().partition(|r| r.success);
+        console!(cli, "Recheck of {}: {} now succeed, {} still fail", path.display(), fixed.len(), still_failing.len());
+        for result in &fixed {
+            console!(cli, "  Fixed: {}", result.url);
+        }
+        for result in &still_failing {
+            console!(cli, "  Still failing: {} - {}", result.url, result.describe());
+        }
+        let urls = |results: &[&WebsiteStatus]| {
+            results.iter().map(|r| serde_json::Value::String(r.url.clone()).to_string()).collect::<Vec<_>>().join(", ")
+        };
+        recheck_section = Some(format!(
+            "\"recheck\": {{\"fixed\": [{}], \"still_failing\": [{}]}}",
+            urls(&fixed),
+            urls(&still_failing)
+        ));
+    }
+
+    if let Some(template) = &cli.html_template {
+        let html = report::render_template(template, results, &summary);
+        match std::fs::write("status.html", html) {
+            Ok(()) => console!(cli, "Report written to status.html"),
+            Err(e) => eprintln!("Failed to write HTML report: {}", e),
+        }
+    }
+    if let Some((template, out)) = &cli.report_template {
+        match template.render(results, &summary, cli.timestamps, cli.time_unit) {
+            Ok(report) => match std::fs::write(out, report) {
+                Ok(()) => console!(cli, "Report written to {}", out.display()),
+                Err(e) => eprintln!("Failed to write report: {}", e),
+            },
+            Err(e) => eprintln!("Failed to render report template: {}", e),
+        }
+    }
+
+    let mut summary_sections = vec![format!("\"results\": {}", summary.to_json_string(cli.time_unit))];
+    summary_sections.extend(recheck_section);
+
+    if !groups.is_empty() {
+        let mut group_sections = Vec::new();
+        for (name, results) in groups {
+            let summary = Summary::from_results(results);
+            console!(
+                cli,
+                "  {}: {} URLs, {} succeeded, {} failed",
+                name,
+                summary.total,
+                summary.succeeded,
+                summary.failed.len()
+            );
+            group_sections.push(format!("\"{}\": {}", name.replace(['"', '\\'], "_"), summary.to_json_string(cli.time_unit)));
+        }
+        summary_sections.push(format!("\"groups\": {{\n    {}\n    }}", group_sections.join(",\n    ")));
+    }
+
+    // Report TLS compliance against the policy minimum
+    if cli.config.tls_info {
+        let compliance = tls::compliance(results, cli.min_tls);
+
+        eprintln!("TLS compliance (minimum {}):", cli.min_tls);
+        for (version, count) in &compliance.counts {
+            eprintln!("  {}: {}", version, count);
+        }
+        eprintln!("  Below minimum: {}", compliance.below_minimum.len());
+        for (url, version) in &compliance.below_minimum {
+            eprintln!("    {} - {}", url, version);
+        }
+
+        summary_sections.push(format!("\"tls\": {}", compliance.to_json_string()));
+    }
+
+    if cli.config.min_response_time_ms.is_some() {
+        let too_fast: Vec<&WebsiteStatus> = results.iter().filter(|r| r.too_fast).collect();
+        console!(cli, "Suspiciously fast responses: {}", too_fast.len());
+        for result in &too_fast {
+            console!(cli, "  {} - {}ms", result.url, result.response_time.as_millis());
+        }
+
+        summary_sections.push(format!(
+            "\"too_fast\": [{}]",
+            too_fast.iter()
+                .map(|r| serde_json::Value::String(r.url.clone()).to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    if cli.config.detect_soft_404 {
+        let suspects: Vec<_> = results.iter().filter(|r| r.possible_soft_404.is_some()).collect();
+        console!(cli, "Possible soft 404s: {}", suspects.len());
+        for result in suspects {
+            console!(cli, "  {} - {}", result.url, result.possible_soft_404.as_deref().unwrap_or_default());
+        }
+
+        summary_sections.push(format!("\"possible_soft_404\": {}", soft404::to_json_string(results)));
+    }
+
+    if cli.connection_stats {
+        let stats = connections::from_results(results);
+
+        eprintln!("Connection reuse (estimated):");
+        for host in &stats {
+            eprintln!(
+                "  {}: {} requests, {} connections, {:.1}% reused",
+                host.host,
+                host.requests,
+                host.connections,
+                host.reused_percent()
+            );
+        }
+
+        summary_sections.push(format!("\"connection_stats\": {}", connections::to_json_string(&stats)));
+    }
+
+    // Certificates about to expire fail the run, not the individual checks
+    let mut expiring = Vec::new();
+    if let Some(window) = cli.fail_cert_within {
+        expiring = tls::expiring_within(results, window);
+
+        if !expiring.is_empty() {
+            eprintln!("CERTIFICATES EXPIRING WITHIN {} DAYS:", window);
+            for cert in &expiring {
+                eprintln!("  {} - expires {} ({} days)", cert.url, cert.not_after, cert.days);
             }
+        }
+
+        summary_sections.push(format!(
+            "\"expiring_certificates\": [{}]",
+            expiring.iter()
+                .map(|cert| cert.to_json_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    // A quorum replaces the default of every URL passing
+    let mut quorum_met = summary.failed.is_empty();
+    if let Some(required) = cli.require_up {
+        quorum_met = summary.succeeded >= required;
+        if quorum_met {
+            console!(cli, "Quorum met: {} of {} URLs up, {} required", summary.succeeded, summary.total, required);
+        } else {
+            eprintln!(
+                "QUORUM NOT MET: only {} of {} URLs up, {} required, {} short",
+                summary.succeeded,
+                summary.total,
+                required,
+                required - summary.succeeded
+            );
+        }
+        summary_sections.push(format!(
+            "\"require_up\": {{\"required\": {}, \"up\": {}, \"met\": {}}}",
+            required, summary.succeeded, quorum_met
+        ));
+    }
+
+    if let Some(summary_file) = &cli.summary_file {
+        let summary_json = format!("{{\n    {}\n}}", summary_sections.join(",\n    "));
+        if let Err(e) = std::fs::write(summary_file, cli.indent_json(summary_json)) {
+            eprintln!("Failed to write summary file: {}", e);
+        } else if cli.verify_output
+            && let Err(e) = output::verify_summary(summary_file)
+        {
+            eprintln!("Output verification failed for {}: {}", summary_file.display(), e);
+        }
+    }
+
+    // A Pushgateway that is down should not fail a run that is otherwise fine
+    let mut pushed = true;
+    if let Some(gateway) = &cli.pushgateway {
+        match prometheus::push(gateway, &cli.job, prometheus::metrics(results), cli.config.timeout) {
+            Ok(()) => console!(cli, "Metrics pushed to {}", gateway),
             Err(e) => {
-                eprintln!("Error reading file: {}", e);
-                std::process::exit(1);
+                eprintln!("Failed to push metrics to {}: {}", gateway, e);
+                pushed = !cli.require_push;
             }
         }
     }
 
-    // Check if we have any URLs to process
-    if urls.is_empty() {
-        print_usage();
+    quorum_met && expiring.is_empty() && pushed
+}
+
+/// Request the URL list at a steady rate for `duration`, print latency
+/// percentiles per URL and overall, and exit non-zero if any request failed
+fn run_load(cli: &Cli, duration: Duration, rps: f64) -> ! {
+    console!(cli, "Sending {} requests per second for {}s", rps, duration.as_secs());
+    let report = website_project::load::run(&cli.urls, &cli.config, duration, rps).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    });
+
+    for stats in report.per_url.iter().chain(std::iter::once(&report.total)) {
+        console!(
+            cli,
+            "{}: {} requests, {} failed, p50 {}ms, p95 {}ms, p99 {}ms",
+            stats.url,
+            stats.requests,
+            stats.failures,
+            stats.p50.as_millis(),
+            stats.p95.as_millis(),
+            stats.p99.as_millis()
+        );
+    }
+    console!(cli, "Achieved {:.1} requests per second", report.achieved_rps());
+
+    if let Some(summary_file) = &cli.summary_file {
+        let summary_json = format!("{{\n    \"load\": {}\n}}", report.to_json_string());
+        if let Err(e) = std::fs::write(summary_file, cli.indent_json(summary_json)) {
+            eprintln!("Failed to write summary file: {}", e);
+        }
+    }
+    std::process::exit(if report.total.failures > 0 { 1 } else { 0 });
+}
+
+/// Combine the `--merge` results files into one results file and summary,
+/// exiting non-zero if any merged result failed
+fn run_merge(cli: &Cli) -> ! {
+    let merged = merge::merge(&cli.merge_files, cli.merge_strategy).unwrap_or_else(|e| {
+        eprintln!("Error reading results: {}", e);
+        std::process::exit(1);
+    });
+    for warning in &merged.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    if merged.duplicates > 0 {
+        console!(cli, "Dropped {} older results of URLs that appear more than once", merged.duplicates);
     }
 
-    // Create HTTP client with timeout
-    let client = Arc::new(
-        Client::builder()
-            .timeout(Duration::from_secs(timeout))
-            .build()
-            .unwrap_or_else(|e| {
-                eprintln!("Failed to create HTTP client: {}", e);
+    let summary = &merged.summary;
+    console!(
+        cli,
+        "Merged {} files: {} URLs, {} succeeded, {} failed",
+        cli.merge_files.len(),
+        summary.total,
+        summary.succeeded,
+        summary.failed.len()
+    );
+    let contents = cli.indent_json(merged.to_json_string());
+    if cli.output_to_stdout() {
+        println!("{}", contents);
+    } else {
+        match cli.write_file(&cli.output_path(), &contents) {
+            Ok(path) => console!(cli, "Results written to {}", path.display()),
+            Err(e) => {
+                eprintln!("Failed to write results file: {}", e);
                 std::process::exit(1);
-            })
+            }
+        }
+    }
+
+    if let Some(summary_file) = &cli.summary_file {
+        let summary_json = format!("{{\n    \"results\": {}\n}}", summary.to_json_string(cli.time_unit));
+        if let Err(e) = std::fs::write(summary_file, cli.indent_json(summary_json)) {
+            eprintln!("Failed to write summary file: {}", e);
+        }
+    }
+    std::process::exit(if summary.failed.is_empty() { 0 } else { 1 });
+}
+
+/// Raise the open file limit when asked and warn when it looks too low for
+/// the number of workers. A limit still too low after raising it is fatal
+fn check_file_limit(cli: &Cli) {
+    let workers = cli.config.max_workers.unwrap_or(cli.config.workers);
+    let needed = limits::required(workers);
+    let Ok((mut soft, hard)) = limits::open_files() else {
+        return;
+    };
+    if let Some(target) = cli.max_file_descriptors {
+        match limits::raise(target) {
+            Ok(raised) if raised > soft => {
+                console!(cli, "Raised the open file limit from {} to {}", soft, raised);
+                soft = raised;
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Failed to raise the open file limit: {}", e),
+        }
+    }
+    if needed <= soft {
+        return;
+    }
+    if cli.max_file_descriptors.is_some() {
+        eprintln!(
+            "Error: {} workers need about {} open files, but the limit is {} (hard limit {})",
+            workers, needed, soft, hard
+        );
+        std::process::exit(1);
+    }
+    eprintln!(
+        "Warning: {} workers may need about {} open files, but the limit is {}; raise it with `ulimit -n {}` or --max-file-descriptors max",
+        workers, needed, soft, needed
     );
+}
 
-    // Create channel for communication between main thread and workers
-    let (sender, receiver) = mpsc::channel::<String>();
-    let receiver = Arc::new(Mutex::new(receiver));
-    let (result_sender, result_receiver) = mpsc::channel::<WebsiteStatus>();
-
-    // Create worker threads
-    let mut handles = Vec::with_capacity(workers);
-    for _ in 0..workers {
-        let client = Arc::clone(&client);
-        let receiver = Arc::clone(&receiver);
-        let result_sender = result_sender.clone();
-        let retries = retries;
-
-        let handle = thread::spawn(move || {
-            while let Ok(url) = {
-                let receiver = receiver.lock().unwrap();
-                receiver.recv()
-            } {
-                let mut last_error = None;
-                let mut response_time = Duration::default();
-                let mut status_code = None;
-
-                for attempt in 0..=retries {
-                    let start = Instant::now();
-                    let result = client.get(&url).send();
-                    let elapsed = start.elapsed();
-
-                    match result {
-                        Ok(response) => {
-                            status_code = Some(response.status().as_u16());
-                            response_time = elapsed;
-                            break;
-                        }
-                        Err(e) => {
-                            last_error = Some(e);
-                            if attempt < retries {
-                                thread::sleep(Duration::from_millis(100));
-                            }
-                        }
-                    }
-                }
-
-                let status = WebsiteStatus {
-                    url: url.clone(),
-                    action_status: match status_code {
-                        Some(code) => Ok(code),
-                        None => Err(last_error.unwrap().to_string()),
-                    },
-                    response_time,
-                    timestamp: SystemTime::now(),
-                };
+/// The config file that reproduces this run: `config` and the front-end
+/// `settings` as its `[cli]` table, or the same as one JSON object
+fn print_config(config: &Config, settings: &Settings, json: bool) -> Result<String, String> {
+    let file = ConfigFile { cli: settings.clone() };
+    if json {
+        let mut value = serde_json::to_value(config).map_err(|e| e.to_string())?;
+        if let serde_json::Value::Object(fields) = &mut value {
+            fields.insert("cli".to_string(), serde_json::to_value(&file.cli).map_err(|e| e.to_string())?);
+        }
+        return serde_json::to_string_pretty(&value).map(|json| json + "\n").map_err(|e| e.to_string());
+    }
+    let cli = toml::to_string(&file).map_err(|e| e.to_string())?;
+    Ok(format!("{}\n{}", config.to_toml()?, cli))
+}
 
-                // Print human-readable output immediately
-                println!(
-                    "{} - {} in {}ms",
-                    status.url,
-                    match status.action_status {
-                        Ok(code) => format!("HTTP {}", code),
-                        Err(ref e) => format!("ERROR: {}", e),
-                    },
-                    status.response_time.as_millis()
-                );
+fn main() {
+    let mut cli = parse_args();
 
-                // Send result to main thread
-                result_sender.send(status).unwrap();
+    if cli.print_config {
+        let (config, settings) = if cli.config.redact_headers {
+            (cli.config.redacted(), cli.settings.redacted())
+        } else {
+            (cli.config.clone(), cli.settings.clone())
+        };
+        let printed = print_config(&config, &settings, cli.print_config_json);
+        match printed {
+            Ok(text) => print!("{}", text),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
             }
-        });
-        handles.push(handle);
+        }
+        return;
     }
 
-    // Send URLs to workers
-    for url in urls {
-        sender.send(url).unwrap_or_else(|e| {
-            eprintln!("Failed to send URL to worker: {}", e);
-        });
+    if !cli.merge_files.is_empty() {
+        run_merge(&cli);
     }
 
-    // Close sender to signal workers to finish
-    drop(sender);
-
-    // Wait for all worker threads to complete
-    for handle in handles {
-        handle.join().unwrap();
+    // Lint the URL list without sending any request
+    if cli.validate_only {
+        let mut invalid = 0;
+        for (url, source) in cli.urls.iter().zip(&cli.sources) {
+            if let Err(e) = targets::validate(url) {
+                eprintln!("{}: {}: {}", source, url, e);
+                invalid += 1;
+            }
+        }
+        println!("Validated {} URLs: {} invalid", cli.urls.len(), invalid);
+        std::process::exit(if invalid > 0 { 1 } else { 0 });
     }
 
-    // Collect all results
-    let mut all_results = Vec::new();
-    while let Ok(status) = result_receiver.recv() {
-        all_results.push(status);
+    check_file_limit(&cli);
+
+    if let Some((duration, rps)) = cli.load {
+        run_load(&cli, duration, rps);
     }
 
-    // Write JSON output
-    let json_string = format!(
-        "[\n{}\n]",
-        all_results.iter()
-            .map(|result| result.to_json_string())
-            .collect::<Vec<_>>()
-            .join(",\n")
-    );
+    let supervisor = Supervisor::start(cli.pid_file.clone(), cli.timestamps, cli.time_unit).unwrap_or_else(|e| {
+        eprintln!("Error writing PID file: {}", e);
+        std::process::exit(1);
+    });
 
-    match File::create("status.json") {
-        Ok(mut file) => {
-            file.write_all(json_string.as_bytes()).unwrap_or_else(|e| {
-                eprintln!("Failed to write JSON file: {}", e);
-            });
-            println!("Results written to status.json");
+    let mut history: Vec<Round> = Vec::new();
+    // Each URL's result in the round before, for up transitions
+    let mut previous: HashMap<String, WebsiteStatus> = HashMap::new();
+    // With per-URL intervals: when each URL is next due and its latest result
+    let mut next_due: HashMap<String, Instant> = HashMap::new();
+    let mut latest: HashMap<String, WebsiteStatus> = HashMap::new();
+    let mut number = 1;
+    loop {
+        let started = Instant::now();
+        let urls = if cli.per_url_intervals {
+            cli.urls.iter().filter(|url| next_due.get(*url).is_none_or(|due| *due <= started)).cloned().collect()
+        } else {
+            cli.urls.clone()
+        };
+        let mut round = scan(&cli, &supervisor, &history, number, urls).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            supervisor.exit(1);
+        });
+        // Report and write every URL's latest result, not only the due ones
+        let checked = if cli.per_url_intervals {
+            for (index, url) in cli.urls.iter().enumerate() {
+                if let Some(result) = round.results.iter().find(|r| &r.url == url) {
+                    next_due.insert(url.clone(), started + cli.url_interval(index).unwrap_or_default());
+                    latest.insert(url.clone(), result.clone());
+                }
+            }
+            let merged = cli.urls.iter().filter_map(|url| latest.get(url).cloned()).collect();
+            std::mem::replace(&mut round.results, merged)
+        } else {
+            round.results.clone()
+        };
+        let groups = if cli.parallel_files { group_results(&cli, &round.results) } else { Vec::new() };
+        let passed = report_round(&cli, &round.results, &groups);
+        update_sizes(&mut cli, &checked);
+        if let Some(endpoint) = &cli.recovery_webhook {
+            notify_recoveries(&cli, endpoint, &previous, &checked);
+            previous.extend(checked.into_iter().map(|r| (r.url.clone(), r)));
         }
-        Err(e) => {
-            eprintln!("Failed to create status.json: {}", e);
+
+        // Write the results file, or one per group
+        if cli.parallel_files && !cli.summary_only {
+            for (name, results) in &groups {
+                let path = output::sibling_path(&cli.output_path(), name);
+                match cli.write_file(&path, &cli.format_results(results)) {
+                    Ok(path) => console!(cli, "Results for {} written to {}", name, path.display()),
+                    Err(e) => eprintln!("Failed to write results file: {}", e),
+                }
+            }
+        } else if !cli.summary_only {
+            // Worker files first, the combined file is the merge of them
+            if let Some(dir) = &cli.per_worker_output {
+                match write_worker_results(&cli, dir, &round.results) {
+                    Ok(()) => console!(cli, "Per-worker results written to {}", dir.display()),
+                    Err(e) => eprintln!("Failed to write per-worker results: {}", e),
+                }
+            }
+            match write_results(&cli, &history, &round) {
+                Ok(Some(path)) => {
+                    console!(cli, "Results written to {}", path.display());
+                    verify_results(&cli, &path);
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("Failed to write results file: {}", e),
+            }
+        }
+
+        let code = match &cli.exit_code_map {
+            _ if passed => 0,
+            Some(map) => map.code(&round.results).unwrap_or(1),
+            None => 1,
+        };
+        let Some(interval) = cli.interval else {
+            supervisor.exit(code);
+        };
+        if cli.rounds.is_some_and(|rounds| number >= rounds) {
+            supervisor.exit(code);
+        }
+
+        if cli.series {
+            history.push(round);
+        }
+        number += 1;
+        let wake = if cli.per_url_intervals {
+            cli.urls.iter().filter_map(|url| next_due.get(url)).min().copied().unwrap_or(started + interval)
+        } else {
+            started + interval
+        };
+        if supervisor.sleep(wake.saturating_duration_since(Instant::now())) {
+            reload_url_files(&mut cli);
+            supervisor.retain(&cli.urls);
+            // A SIGHUP asks for a check of everything, due or not
+            next_due.clear();
+            latest.retain(|url, _| cli.urls.contains(url));
         }
     }
 }
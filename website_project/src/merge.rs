@@ -0,0 +1,207 @@
+//! Combining the results files of several runs, such as URL shards checked
+//! on different machines, into one file with one summary.
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::DateTime;
+use flate2::read::GzDecoder;
+use serde_json::{Map, Value};
+
+use crate::{summary::percentile, Summary};
+
+/// What happens to a URL that appears in more than one result
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Keep every result
+    #[default]
+    All,
+    /// Keep only the result with the latest timestamp, at the position of
+    /// the URL's first result. Ties go to the later file
+    Latest,
+}
+
+/// Results of every input file, in file order
+pub struct Merged {
+    /// Each result as it was read, fields the checker does not know included
+    pub results: Vec<Map<String, Value>>,
+    pub summary: Summary,
+    /// Problems in the inputs that were worked around
+    pub warnings: Vec<String>,
+    /// Older results dropped by [`MergeStrategy::Latest`]
+    pub duplicates: usize,
+}
+
+impl Merged {
+    /// The results as a JSON array laid out like a results file
+    pub fn to_json_string(&self) -> String {
+        let results: Vec<String> = self.results.iter()
+            .map(|result| {
+                let fields: Vec<String> = result.iter()
+                    .map(|(name, value)| format!("{}: {}", Value::String(name.clone()), value))
+                    .collect();
+                format!("{{\n    {}\n}}", fields.join(",\n    "))
+            })
+            .collect();
+        format!("[\n{}\n]", results.join(",\n"))
+    }
+}
+
+/// Read and combine results files. Each may be a JSON results array, a
+/// `--series` file, of which the latest round is used, or NDJSON, and may be
+/// gzip compressed. Results without a `url` are dropped, ones without a
+/// boolean `success` get it from their status, so files from older or newer
+/// versions still merge. Files whose timestamps are written differently, one
+/// as Unix seconds and one as RFC 3339, are rejected, the output could not
+/// keep both
+pub fn merge(paths: &[PathBuf], strategy: MergeStrategy) -> Result<Merged, String> {
+    let mut merged = Merged { results: Vec::new(), summary: summarize(&[]), warnings: Vec::new(), duplicates: 0 };
+    // The first file with timestamps, and whether they are RFC 3339
+    let mut timestamps: Option<(&Path, bool)> = None;
+    for path in paths {
+        let contents = read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let (results, warning) = parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if let Some(warning) = warning {
+            merged.warnings.push(format!("{}: {}", path.display(), warning));
+        }
+        if let Some(rfc3339) = results.iter().find_map(|r| r.get("timestamp")).map(Value::is_string) {
+            match timestamps {
+                Some((first, first_rfc3339)) if first_rfc3339 != rfc3339 => {
+                    let name = |rfc3339| if rfc3339 { "rfc3339" } else { "unix" };
+                    return Err(format!(
+                        "{} has {} timestamps but {} has {}, re-run with the same --timestamp-format",
+                        path.display(),
+                        name(rfc3339),
+                        first.display(),
+                        name(first_rfc3339)
+                    ));
+                }
+                Some(_) => {}
+                None => timestamps = Some((path, rfc3339)),
+            }
+        }
+        for (index, result) in results.into_iter().enumerate() {
+            match normalize(result) {
+                Ok((result, None)) => merged.results.push(result),
+                Ok((result, Some(warning))) => {
+                    merged.warnings.push(format!("{}: result {}: {}", path.display(), index + 1, warning));
+                    merged.results.push(result);
+                }
+                Err(e) => merged.warnings.push(format!("{}: result {}: {}, skipped", path.display(), index + 1, e)),
+            }
+        }
+    }
+    if strategy == MergeStrategy::Latest {
+        let total = merged.results.len();
+        merged.results = latest(merged.results);
+        merged.duplicates = total - merged.results.len();
+    }
+    merged.summary = summarize(&merged.results);
+    Ok(merged)
+}
+
+/// One result per URL, the one with the latest timestamp
+fn latest(results: Vec<Map<String, Value>>) -> Vec<Map<String, Value>> {
+    let mut kept: Vec<Map<String, Value>> = Vec::new();
+    for result in results {
+        match kept.iter_mut().find(|kept| kept["url"] == result["url"]) {
+            Some(kept) if timestamp(&result) >= timestamp(kept) => *kept = result,
+            Some(_) => {}
+            None => kept.push(result),
+        }
+    }
+    kept
+}
+
+/// Seconds since the epoch of a result, `None` sorting before any time
+fn timestamp(result: &Map<String, Value>) -> Option<i64> {
+    match result.get("timestamp")? {
+        Value::Number(seconds) => seconds.as_i64(),
+        Value::String(time) => DateTime::parse_from_rfc3339(time).ok().map(|time| time.timestamp()),
+        _ => None,
+    }
+}
+
+fn read(path: &Path) -> io::Result<String> {
+    let mut contents = String::new();
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        GzDecoder::new(File::open(path)?).read_to_string(&mut contents)?;
+    } else {
+        File::open(path)?.read_to_string(&mut contents)?;
+    }
+    Ok(contents)
+}
+
+/// The result values of one file, and a note when not all of it was used
+fn parse(contents: &str) -> Result<(Vec<Value>, Option<String>), String> {
+    if !contents.trim_start().starts_with('[') {
+        let results = contents.lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(index, line)| serde_json::from_str(line).map_err(|e| format!("line {}: {}", index + 1, e)))
+            .collect::<Result<_, _>>()?;
+        return Ok((results, None));
+    }
+    let mut items = match serde_json::from_str(contents).map_err(|e| e.to_string())? {
+        Value::Array(items) => items,
+        _ => return Err("not a results array".to_string()),
+    };
+    let is_series = items.first().is_some_and(|item| item.get("round").is_some() && item.get("results").is_some());
+    if !is_series {
+        return Ok((items, None));
+    }
+    let mut round = items.pop().expect("a series has a first round");
+    let warning = (!items.is_empty()).then(|| format!("series file, using only its latest round {}", round["round"]));
+    match round["results"].take() {
+        Value::Array(results) => Ok((results, warning)),
+        _ => Err("round without a results array".to_string()),
+    }
+}
+
+/// Check a result has a URL and fill in `success` when it is missing
+fn normalize(result: Value) -> Result<(Map<String, Value>, Option<String>), String> {
+    let Value::Object(mut result) = result else {
+        return Err("not a JSON object".to_string());
+    };
+    if !result.get("url").is_some_and(Value::is_string) {
+        return Err("no url".to_string());
+    }
+    if result.get("success").is_some_and(Value::is_boolean) {
+        return Ok((result, None));
+    }
+    let success = result.get("status").and_then(Value::as_u64).is_some_and(|code| (200..300).contains(&code));
+    result.insert("success".to_string(), Value::Bool(success));
+    Ok((result, Some(format!("no success field, taken as {} from the status", success))))
+}
+
+/// Response time of a result written with any `--time-unit`
+fn response_time(result: &Map<String, Value>) -> Duration {
+    [("response_time_ms", 1e3), ("response_time_us", 1e6), ("response_time_s", 1.0)]
+        .into_iter()
+        .find_map(|(name, per_second)| result.get(name).and_then(Value::as_f64).map(|value| value / per_second))
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .unwrap_or_default()
+}
+
+fn summarize(results: &[Map<String, Value>]) -> Summary {
+    let mut times: Vec<Duration> = results.iter()
+        .map(response_time)
+        .collect();
+    times.sort();
+    let succeeded = |r: &&Map<String, Value>| r["success"] == Value::Bool(true);
+    Summary {
+        total: results.len(),
+        succeeded: results.iter().filter(succeeded).count(),
+        failed: results.iter()
+            .filter(|r| !succeeded(r))
+            .map(|r| r["url"].as_str().unwrap_or_default().to_string())
+            .collect(),
+        p50: percentile(&times, 50.0),
+        p95: percentile(&times, 95.0),
+        p99: percentile(&times, 99.0),
+    }
+}
@@ -0,0 +1,444 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::Serialize;
+use serde_json::{ser::PrettyFormatter, Serializer, Value};
+
+use crate::random::Rng;
+
+use crate::{status::escape_json, TimeUnit, TimestampFormat, WebsiteStatus};
+
+/// Results as a pretty printed JSON array
+pub fn json_array(results: &[WebsiteStatus], timestamps: TimestampFormat, unit: TimeUnit) -> String {
+    format!(
+        "[\n{}\n]",
+        results.iter()
+            .map(|result| result.to_json_string(timestamps, unit))
+            .collect::<Vec<_>>()
+            .join(",\n")
+    )
+}
+
+/// Re-indent a pretty printed JSON document with `indent` per level, such
+/// as two spaces or a tab. Documents that do not parse are returned as they
+/// are
+pub fn reindent(json: &str, indent: &str) -> String {
+    let Ok(value) = serde_json::from_str::<Value>(json) else {
+        return json.to_string();
+    };
+    let mut out = Vec::new();
+    let mut serializer = Serializer::with_formatter(&mut out, PrettyFormatter::with_indent(indent.as_bytes()));
+    match value.serialize(&mut serializer) {
+        Ok(()) => String::from_utf8(out).unwrap_or_else(|_| json.to_string()),
+        Err(_) => json.to_string(),
+    }
+}
+
+/// Quote a logfmt value when it is empty or holds spaces, quotes or `=`
+fn logfmt_value(value: &str) -> String {
+    if value.is_empty() || value.contains([' ', '"', '=', '\\']) || value.contains(char::is_control) {
+        format!("\"{}\"", escape_json(value))
+    } else {
+        value.to_string()
+    }
+}
+
+/// A result as one logfmt line, e.g. `at=check url=https://example.com
+/// status=200 response_time_ms=45 timestamp=1700000000 success=true`.
+/// Failed checks carry `error` instead of `status`
+pub fn logfmt_line(result: &WebsiteStatus, timestamps: TimestampFormat, unit: TimeUnit) -> String {
+    let mut fields = vec![
+        "at=check".to_string(),
+        format!("url={}", logfmt_value(&result.url)),
+    ];
+    match &result.action_status {
+        Ok(code) => fields.push(format!("status={}", code)),
+        Err(e) => fields.push(format!("error={}", logfmt_value(e))),
+    }
+    fields.push(format!("response_time_{}={}", unit.suffix(), unit.format(result.response_time)));
+    fields.push(format!("timestamp={}", logfmt_value(&timestamps.format(result.timestamp))));
+    fields.push(format!("success={}", result.success));
+    fields.join(" ")
+}
+
+/// Results as logfmt, one line each
+pub fn logfmt_lines(results: &[WebsiteStatus], timestamps: TimestampFormat, unit: TimeUnit) -> String {
+    results.iter()
+        .map(|result| logfmt_line(result, timestamps, unit) + "\n")
+        .collect()
+}
+
+/// Field separator and quoting rules for CSV output
+#[derive(Debug, Clone, Copy)]
+pub struct CsvWriter {
+    pub delimiter: char,
+    /// Quote every field, not just the ones that need it
+    pub always_quote: bool,
+}
+
+impl Default for CsvWriter {
+    fn default() -> Self {
+        CsvWriter { delimiter: ',', always_quote: false }
+    }
+}
+
+impl CsvWriter {
+    /// Quote a field when it holds the delimiter, a quote or a line break,
+    /// doubling any quotes inside
+    fn field(&self, value: &str) -> String {
+        if self.always_quote || value.contains([self.delimiter, '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+
+    /// One CSV record, terminated by a newline
+    pub fn row<S: AsRef<str>>(&self, fields: &[S]) -> String {
+        let mut row = fields.iter()
+            .map(|field| self.field(field.as_ref()))
+            .collect::<Vec<_>>()
+            .join(&self.delimiter.to_string());
+        row.push('\n');
+        row
+    }
+}
+
+/// Results as CSV with a header row. Failed checks have an empty `status`
+/// and the message in `error`
+pub fn csv_table(results: &[WebsiteStatus], timestamps: TimestampFormat, unit: TimeUnit, writer: CsvWriter) -> String {
+    let response_time = format!("response_time_{}", unit.suffix());
+    let mut table = writer.row(&["url", "status", "error", &response_time, "timestamp", "success"]);
+    for result in results {
+        let (status, error) = match &result.action_status {
+            Ok(code) => (code.to_string(), String::new()),
+            Err(e) => (String::new(), e.clone()),
+        };
+        table.push_str(&writer.row(&[
+            result.url.clone(),
+            status,
+            error,
+            unit.format(result.response_time),
+            timestamps.format(result.timestamp),
+            result.success.to_string(),
+        ]));
+    }
+    table
+}
+
+/// Results as newline-delimited JSON, one object per line tagged with `run_id`
+pub fn ndjson_lines(results: &[WebsiteStatus], timestamps: TimestampFormat, unit: TimeUnit, run_id: &str) -> String {
+    results.iter()
+        .map(|result| result.to_json_line(timestamps, unit, run_id) + "\n")
+        .collect()
+}
+
+/// A URL as one Graphite path component: everything but ASCII letters,
+/// digits, `-` and `_` becomes `_`, so `https://example.com/a` turns into
+/// `https___example_com_a`
+fn graphite_component(url: &str) -> String {
+    url.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// A result as Graphite plaintext lines, e.g.
+/// `website.response_time.https___example_com 45 1700000000` and
+/// `website.is_up.https___example_com 1 1700000000`. `prefix` is prepended
+/// as a namespace
+pub fn graphite_lines(result: &WebsiteStatus, prefix: Option<&str>) -> String {
+    let prefix = prefix.map_or(String::new(), |prefix| format!("{}.", prefix.trim_end_matches('.')));
+    let url = graphite_component(&result.url);
+    let timestamp = result.timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    format!(
+        "{prefix}website.response_time.{url} {} {timestamp}\n{prefix}website.is_up.{url} {} {timestamp}\n",
+        result.response_time.as_millis(),
+        u8::from(result.success)
+    )
+}
+
+/// Send Graphite plaintext lines to a carbon server over TCP
+pub fn send_graphite(server: &str, contents: &[u8], timeout: Duration) -> io::Result<()> {
+    let addr = server
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "failed to resolve Graphite server"))?;
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_write_timeout(Some(timeout))?;
+    stream.write_all(contents)?;
+    stream.flush()
+}
+
+/// Run ID made of the start time and a random suffix, e.g. `1700000000-3f9a2c1e`
+pub fn generate_run_id(rng: &Rng) -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let suffix = rng.value("run-id") as u32;
+    format!("{}-{:08x}", seconds, suffix)
+}
+
+/// Results of one pass over the URL list in interval mode
+pub struct Round {
+    /// 1-based round number
+    pub number: u64,
+    /// When the round started
+    pub timestamp: SystemTime,
+    pub results: Vec<WebsiteStatus>,
+}
+
+/// Rounds as a JSON time series, each with its number, start time and results
+pub fn series_json<'a>(rounds: impl IntoIterator<Item = &'a Round>, timestamps: TimestampFormat, unit: TimeUnit) -> String {
+    format!(
+        "[\n{}\n]",
+        rounds.into_iter()
+            .map(|round| {
+                format!(
+                    "{{\n\"round\": {},\n\"timestamp\": {},\n\"results\": {}\n}}",
+                    round.number,
+                    timestamps.to_json(round.timestamp),
+                    json_array(&round.results, timestamps, unit)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",\n")
+    )
+}
+
+/// Read a written file back, decompressing `.gz` files
+fn read_back(path: &Path) -> Result<String, String> {
+    let mut contents = String::new();
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let read = if path.extension().is_some_and(|extension| extension == "gz") {
+        GzDecoder::new(file).read_to_string(&mut contents)
+    } else {
+        { file }.read_to_string(&mut contents)
+    };
+    read.map_err(|e| e.to_string())?;
+    Ok(contents)
+}
+
+/// Check that a JSON value has the fields every result is written with
+fn verify_result(result: &Value, unit: TimeUnit) -> Result<(), String> {
+    let object = result.as_object().ok_or("result is not an object")?;
+    let response_time = format!("response_time_{}", unit.suffix());
+    for field in ["url", "status", response_time.as_str(), "timestamp", "success"] {
+        let Some(value) = object.get(field) else {
+            return Err(format!("result without {}", field));
+        };
+        let valid = match field {
+            "url" => value.is_string(),
+            "status" | "timestamp" => value.is_number() || value.is_string(),
+            "success" => value.is_boolean(),
+            _ => value.is_number(),
+        };
+        if !valid {
+            return Err(format!("result with an invalid {}: {}", field, value));
+        }
+    }
+    Ok(())
+}
+
+/// Read a results file back and check that it parses as JSON results: an
+/// array of them, a series of rounds holding them, or NDJSON lines. Returns
+/// how many results it holds
+pub fn verify_results(path: &Path, unit: TimeUnit) -> Result<usize, String> {
+    let contents = read_back(path)?;
+    let results: Vec<Value> = if contents.trim_start().starts_with('[') {
+        let parsed: Vec<Value> = serde_json::from_str(&contents).map_err(|e| format!("invalid JSON: {}", e))?;
+        let mut results = Vec::new();
+        for entry in parsed {
+            match entry.get("round").and(entry.get("results")) {
+                Some(Value::Array(round)) => results.extend(round.iter().cloned()),
+                Some(_) => return Err("round without a results array".to_string()),
+                None => results.push(entry),
+            }
+        }
+        results
+    } else {
+        contents.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(number, line)| serde_json::from_str(line).map_err(|e| format!("invalid JSON on line {}: {}", number + 1, e)))
+            .collect::<Result<_, _>>()?
+    };
+    for result in &results {
+        verify_result(result, unit)?;
+    }
+    Ok(results.len())
+}
+
+/// Read a summary file back and check that it parses as a JSON object with
+/// the counts of its `results` section
+pub fn verify_summary(path: &Path) -> Result<(), String> {
+    let summary: Value = serde_json::from_str(&read_back(path)?).map_err(|e| format!("invalid JSON: {}", e))?;
+    let results = summary.get("results").ok_or("summary without results")?;
+    for field in ["total", "succeeded"] {
+        if !results.get(field).is_some_and(Value::is_u64) {
+            return Err(format!("summary without a valid {}", field));
+        }
+    }
+    Ok(())
+}
+
+/// Compression level used when none is given
+pub const DEFAULT_GZIP_LEVEL: u32 = 6;
+
+/// `dir/status.json` becomes `dir/status.<label>.json`
+pub fn sibling_path(path: &Path, label: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, label, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, label),
+    };
+    path.with_file_name(name)
+}
+
+/// Split results over `status.part1.json`, `status.part2.json`, ... next to
+/// `path`, each a standalone array of at most `per_file` results, and list
+/// the parts in `status.manifest.json`, which is written last. Returns the
+/// manifest path
+pub fn write_parts(
+    path: &Path,
+    gzip: Option<u32>,
+    results: &[WebsiteStatus],
+    per_file: usize,
+    timestamps: TimestampFormat,
+    unit: TimeUnit,
+) -> io::Result<PathBuf> {
+    let mut parts = Vec::new();
+    for (n, chunk) in results.chunks(per_file.max(1)).enumerate() {
+        let part = sibling_path(path, &format!("part{}", n + 1));
+        let written = write_atomic(&part, gzip, json_array(chunk, timestamps, unit).as_bytes())?;
+        parts.push(format!(
+            "{{\"file\": \"{}\", \"results\": {}}}",
+            escape_json(&written.file_name().unwrap_or_default().to_string_lossy()),
+            chunk.len()
+        ));
+    }
+
+    let manifest = format!(
+        "{{\n    \"total_results\": {},\n    \"parts\": [\n        {}\n    ]\n}}",
+        results.len(),
+        parts.join(",\n        ")
+    );
+    write_atomic(&sibling_path(path, "manifest"), None, manifest.as_bytes())
+}
+
+/// Add lines to the end of the output file, creating it when missing.
+/// Returns the path written
+pub fn append(path: &Path, contents: &[u8]) -> io::Result<PathBuf> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(contents)?;
+    Ok(path.to_path_buf())
+}
+
+/// Rotate `path` logrotate style once it has grown to `limit` bytes: `path`
+/// becomes `path.1`, `path.1` becomes `path.2` and so on, the oldest of
+/// `count` kept files dropping off. The next write then starts a fresh
+/// file. Returns whether it rotated
+pub fn rotate(path: &Path, limit: u64, count: u32) -> io::Result<bool> {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.len() >= limit => {}
+        Ok(_) => return Ok(false),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    }
+    let numbered = |n: u32| {
+        let mut numbered = path.as_os_str().to_owned();
+        numbered.push(format!(".{}", n));
+        PathBuf::from(numbered)
+    };
+    if count == 0 {
+        std::fs::remove_file(path)?;
+        return Ok(true);
+    }
+    for n in (1..count).rev() {
+        match std::fs::rename(numbered(n), numbered(n + 1)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    std::fs::rename(path, numbered(1))?;
+    Ok(true)
+}
+
+/// Path the output is written to, with `.gz` appended when compressing
+pub fn final_path(path: &Path, gzip: Option<u32>) -> PathBuf {
+    if gzip.is_some() {
+        let mut gz_path = path.as_os_str().to_owned();
+        gz_path.push(".gz");
+        PathBuf::from(gz_path)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Replace the output file in one step: the contents go to a temporary
+/// file next to it which is then renamed over the old one, so readers and
+/// crashes never see a half-written file. `gzip` is the compression
+/// level, if any. Returns the path written
+pub fn write_atomic(path: &Path, gzip: Option<u32>, contents: &[u8]) -> io::Result<PathBuf> {
+    let target = final_path(path, gzip);
+    let mut tmp_path = target.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let mut writer = OutputWriter::create_exact(&tmp_path, gzip)?;
+    writer.write_all(contents)?;
+    writer.finish()?;
+    std::fs::rename(&tmp_path, &target)?;
+    Ok(target)
+}
+
+/// Destination for result files, optionally gzip compressed
+pub enum OutputWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl OutputWriter {
+    /// Create a writer for exactly `path`, without adding an extension.
+    /// With a `gzip` level set everything written is compressed at that level
+    fn create_exact(path: &Path, gzip: Option<u32>) -> io::Result<OutputWriter> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(match gzip {
+            Some(level) => OutputWriter::Gzip(GzEncoder::new(file, Compression::new(level))),
+            None => OutputWriter::Plain(file),
+        })
+    }
+
+    /// Flush buffered data and, when compressing, write the gzip trailer
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            OutputWriter::Plain(mut file) => file.flush(),
+            OutputWriter::Gzip(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputWriter::Plain(file) => file.write(buf),
+            OutputWriter::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputWriter::Plain(file) => file.flush(),
+            OutputWriter::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
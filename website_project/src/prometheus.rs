@@ -0,0 +1,60 @@
+//! Results in the Prometheus text exposition format, pushed to a
+//! Pushgateway for batch and cron runs that cannot be scraped.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use reqwest::{blocking::Client, Url};
+
+use crate::WebsiteStatus;
+
+/// Escape a label value: backslashes, double quotes and line feeds
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Gauges for every URL, labelled with it: `website_up`,
+/// `website_response_time_seconds` and, for checks that got a status code,
+/// `website_status_code`. A URL checked more than once reports its last result
+pub fn metrics(results: &[WebsiteStatus]) -> String {
+    let latest: BTreeMap<&str, &WebsiteStatus> = results.iter().map(|result| (result.url.as_str(), result)).collect();
+    let mut families = [
+        ("website_up", "Whether the check of the URL succeeded", String::new()),
+        ("website_response_time_seconds", "Response time of the check", String::new()),
+        ("website_status_code", "Status code of the response", String::new()),
+    ];
+    for (url, result) in latest {
+        let labels = format!("{{url=\"{}\"}}", escape_label(url));
+        families[0].2.push_str(&format!("website_up{} {}\n", labels, u8::from(result.success)));
+        families[1].2.push_str(&format!("website_response_time_seconds{} {}\n", labels, result.response_time.as_secs_f64()));
+        if let Ok(code) = result.action_status {
+            families[2].2.push_str(&format!("website_status_code{} {}\n", labels, code));
+        }
+    }
+    families.iter()
+        .filter(|(_, _, samples)| !samples.is_empty())
+        .map(|(name, help, samples)| format!("# HELP {name} {help}\n# TYPE {name} gauge\n{samples}"))
+        .collect()
+}
+
+/// POST metrics to a Pushgateway under `job`, replacing the job's earlier
+/// values of the same metrics. Fails on transport errors and non-2xx replies
+pub fn push(gateway: &str, job: &str, metrics: String, timeout: Duration) -> Result<(), String> {
+    let mut url = Url::parse(gateway).map_err(|e| e.to_string())?;
+    url.path_segments_mut()
+        .map_err(|_| "not a base URL".to_string())?
+        .pop_if_empty()
+        .extend(["metrics", "job", job]);
+    let client = Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client.post(url)
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .body(metrics)
+        .send()
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status().as_u16()));
+    }
+    Ok(())
+}
@@ -0,0 +1,44 @@
+//! Random values of a run, reproducible with a seed.
+//!
+//! Values are derived from the seed and a key naming what they are for,
+//! rather than drawn in turn from one generator, so they do not depend on
+//! the order in which workers happen to ask for them.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+/// Source of every random value of a run
+#[derive(Debug, Clone, Copy)]
+pub struct Rng {
+    seed: u64,
+}
+
+impl Rng {
+    /// Values are fixed by `seed`, or random per process without one
+    pub fn new(seed: Option<u64>) -> Self {
+        // RandomState is seeded randomly per process
+        let seed = seed.unwrap_or_else(|| RandomState::new().build_hasher().finish());
+        Rng { seed }
+    }
+
+    /// The random value for `key`, the same on every call
+    pub fn value(&self, key: &str) -> u64 {
+        // FNV-1a over the key, then the SplitMix64 finalizer to spread it
+        let mut hash = 0xcbf29ce484222325u64;
+        for byte in key.bytes() {
+            hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+        }
+        let mut z = self.seed.wrapping_add(hash).wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::new(None)
+    }
+}
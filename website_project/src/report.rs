@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use minijinja::{context, Environment, Value};
+
+use crate::{Summary, TimeUnit, TimestampFormat, WebsiteStatus};
+
+/// Escape text for HTML element content and attribute values
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Results as an HTML table, one row per check
+pub fn html_results(results: &[WebsiteStatus]) -> String {
+    let rows: String = results
+        .iter()
+        .map(|result| {
+            format!(
+                "    <tr class=\"{}\"><td>{}</td><td>{}</td><td>{}ms</td></tr>\n",
+                if result.success { "up" } else { "down" },
+                escape_html(&result.url),
+                escape_html(&result.describe()),
+                result.response_time.as_millis()
+            )
+        })
+        .collect();
+
+    format!(
+        "<table>\n  <thead>\n    <tr><th>URL</th><th>Status</th><th>Response time</th></tr>\n  </thead>\n  <tbody>\n{}  </tbody>\n</table>",
+        rows
+    )
+}
+
+/// Summary counts and failing URLs as an HTML fragment
+pub fn html_summary(summary: &Summary) -> String {
+    let mut html = format!(
+        "<p>Checked {} URLs: {} succeeded, {} failed</p>",
+        summary.total,
+        summary.succeeded,
+        summary.failed.len()
+    );
+    if !summary.failed.is_empty() {
+        html.push_str("\n<ul>\n");
+        for url in &summary.failed {
+            html.push_str(&format!("  <li>{}</li>\n", escape_html(url)));
+        }
+        html.push_str("</ul>");
+    }
+    html
+}
+
+/// Fill a user supplied template, replacing `{{results}}` and `{{summary}}`
+pub fn render_template(template: &str, results: &[WebsiteStatus], summary: &Summary) -> String {
+    template
+        .replace("{{results}}", &html_results(results))
+        .replace("{{summary}}", &html_summary(summary))
+}
+
+/// A user supplied report template in Jinja syntax, such as a branded HTML
+/// page or a Markdown status report. Values are HTML escaped when the file
+/// name ends in `.html`, `.htm` or `.xml` and written as they are otherwise
+pub struct ReportTemplate {
+    env: Environment<'static>,
+    name: String,
+}
+
+impl ReportTemplate {
+    /// Read a template, failing on syntax errors before anything is checked
+    pub fn load(path: &Path) -> Result<ReportTemplate, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let mut env = Environment::new();
+        env.set_keep_trailing_newline(true);
+        env.add_template_owned(name.clone(), source)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(ReportTemplate { env, name })
+    }
+
+    /// Render the template. `results` is every result with the fields of the
+    /// JSON results file and `summary` the `results` section of the summary
+    /// file
+    pub fn render(
+        &self,
+        results: &[WebsiteStatus],
+        summary: &Summary,
+        timestamps: TimestampFormat,
+        unit: TimeUnit,
+    ) -> Result<String, String> {
+        let json = |text: String| serde_json::from_str::<serde_json::Value>(&text).map_err(|e| e.to_string());
+        let results = results.iter()
+            .map(|result| json(result.to_json_string(timestamps, unit)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let summary = json(summary.to_json_string(unit))?;
+        self.env.get_template(&self.name)
+            .and_then(|template| {
+                template.render(context! {
+                    results => Value::from_serialize(&results),
+                    summary => Value::from_serialize(&summary),
+                })
+            })
+            .map_err(|e| e.to_string())
+    }
+}
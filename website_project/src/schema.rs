@@ -0,0 +1,62 @@
+//! JSON Schema validation of API responses.
+
+use std::fmt;
+
+use jsonschema::Validator;
+use serde_json::Value;
+
+/// Violations listed per response, the rest are only counted
+const MAX_SCHEMA_ERRORS: usize = 10;
+
+/// A compiled JSON Schema and the file it was read from
+pub struct JsonSchema {
+    path: String,
+    validator: Validator,
+}
+
+impl JsonSchema {
+    /// Read and compile the schema in `path`
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let schema: Value = serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?;
+        let validator = jsonschema::validator_for(&schema).map_err(|e| format!("{}: invalid schema: {}", path, e))?;
+        Ok(JsonSchema { path: path.to_string(), validator })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The violations of `body`, each prefixed with the JSON pointer of the
+    /// offending value, empty when it is valid
+    pub fn errors(&self, body: &[u8]) -> Vec<String> {
+        match serde_json::from_slice(body) {
+            Ok(instance) => self.value_errors(&instance),
+            Err(e) => vec![format!("response is not JSON: {}", e)],
+        }
+    }
+
+    /// The violations of a parsed value, like [`JsonSchema::errors`]
+    pub fn value_errors(&self, instance: &Value) -> Vec<String> {
+        let mut errors: Vec<String> = Vec::new();
+        let mut total = 0;
+        for error in self.validator.iter_errors(instance) {
+            total += 1;
+            if errors.len() < MAX_SCHEMA_ERRORS {
+                let pointer = error.instance_path().to_string();
+                let pointer = if pointer.is_empty() { "/".to_string() } else { pointer };
+                errors.push(format!("{}: {}", pointer, error));
+            }
+        }
+        if total > errors.len() {
+            errors.push(format!("and {} more", total - errors.len()));
+        }
+        errors
+    }
+}
+
+impl fmt::Debug for JsonSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonSchema").field("path", &self.path).finish()
+    }
+}
@@ -0,0 +1,63 @@
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+/// Read one SMTP reply, following `250-` style continuation lines, and
+/// return the reply code with the first line of text
+fn read_reply(reader: &mut impl BufRead) -> Result<(u16, String), String> {
+    let mut first_line = None;
+    loop {
+        let mut line = String::new();
+        let read = reader.read_line(&mut line).map_err(|e| match e.kind() {
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => "timed out waiting for reply".to_string(),
+            _ => e.to_string(),
+        })?;
+        if read == 0 {
+            return Err("connection closed by server".to_string());
+        }
+        let line = line.trim_end().to_string();
+        let code = line.get(..3)
+            .and_then(|code| code.parse::<u16>().ok())
+            .ok_or_else(|| format!("invalid SMTP reply: {}", line))?;
+        let last = line.as_bytes().get(3) != Some(&b'-');
+        let first = first_line.get_or_insert(line);
+        if last {
+            return Ok((code, first.clone()));
+        }
+    }
+}
+
+/// Connect to an SMTP server, check the `220` greeting and optionally the
+/// `250` reply to `EHLO`, then say `QUIT`. Returns the greeting line
+pub fn check(addr: &str, ehlo: bool, timeout: Duration) -> Result<String, String> {
+    let addr = addr
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or("failed to resolve domain")?;
+    let mut stream = TcpStream::connect_timeout(&addr, timeout).map_err(|e| e.to_string())?;
+    stream.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+
+    let (code, banner) = read_reply(&mut reader)?;
+    if code != 220 {
+        return Err(format!("unexpected SMTP banner: {}", banner));
+    }
+
+    if ehlo {
+        stream.write_all(b"EHLO checker\r\n").map_err(|e| e.to_string())?;
+        let (code, reply) = read_reply(&mut reader)?;
+        if code != 250 {
+            return Err(format!("EHLO rejected: {}", reply));
+        }
+    }
+
+    // The check has already passed, a server hanging up early is not an error
+    let _ = stream.write_all(b"QUIT\r\n");
+    let _ = read_reply(&mut reader);
+
+    Ok(banner)
+}
@@ -0,0 +1,73 @@
+//! Soft 404 detection: sites that answer 200 with an error page for paths
+//! that do not exist, which status codes alone never reveal.
+
+use std::io::Read;
+
+use reqwest::{blocking::Client, Url};
+
+use crate::{bodies::fnv1a, status::escape_json, Config, WebsiteStatus};
+
+/// Length and hash of a response body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub length: usize,
+    pub hash: u32,
+}
+
+impl Fingerprint {
+    pub fn of(body: &[u8]) -> Self {
+        Fingerprint { length: body.len(), hash: fnv1a(body) }
+    }
+
+    /// Why a page looks like the one served for a missing path, if it does.
+    /// Bodies of equal length count too, error templates often differ only
+    /// in a fixed-width request ID or timestamp
+    pub fn resemblance(&self, missing: &Fingerprint) -> Option<&'static str> {
+        if self.hash == missing.hash && self.length == missing.length {
+            Some("identical to")
+        } else if self.length == missing.length {
+            Some("same length as")
+        } else {
+            None
+        }
+    }
+}
+
+/// Request a path of `url`'s host that should not exist and fingerprint the
+/// answer. Returns the URL requested and the fingerprint when the host
+/// answered with a 2xx, `None` when it reports missing pages properly or
+/// could not be reached. `token` makes the path unlikely to exist
+pub(crate) fn missing_page(client: &Client, config: &Config, url: &str, token: u32) -> Option<(String, Fingerprint)> {
+    let mut missing = Url::parse(url).ok()?;
+    missing.set_path(&format!("/website-status-checker-{:08x}", token));
+    missing.set_query(None);
+
+    let mut request = client.get(missing.clone());
+    for (name, value) in &config.headers {
+        request = request.header(name, value);
+    }
+    let mut response = request.send().ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    let mut body = Vec::new();
+    response.read_to_end(&mut body).ok()?;
+    Some((missing.to_string(), Fingerprint::of(&body)))
+}
+
+/// Possible soft 404s of a scan with the reason each was flagged, as a JSON
+/// array of `{"url": ..., "reason": ...}`
+pub fn to_json_string(results: &[WebsiteStatus]) -> String {
+    format!(
+        "[{}]",
+        results.iter()
+            .filter_map(|r| r.possible_soft_404.as_ref().map(|reason| (&r.url, reason)))
+            .map(|(url, reason)| format!(
+                "{{\"url\": \"{}\", \"reason\": \"{}\"}}",
+                escape_json(url),
+                escape_json(reason)
+            ))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
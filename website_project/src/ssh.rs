@@ -0,0 +1,52 @@
+use std::{
+    io::{BufRead, BufReader, ErrorKind},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+/// Lines a server may send before its version string (RFC 4253 4.2)
+const MAX_PREAMBLE_LINES: usize = 16;
+
+/// Connect to an SSH server and return its version string,
+/// e.g. `SSH-2.0-OpenSSH_8.9p1 Ubuntu-3`
+pub fn check(addr: &str, timeout: Duration) -> Result<String, String> {
+    let addr = addr
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or("failed to resolve domain")?;
+    let stream = TcpStream::connect_timeout(&addr, timeout).map_err(|e| e.to_string())?;
+    stream.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(stream);
+
+    for _ in 0..MAX_PREAMBLE_LINES {
+        let mut line = String::new();
+        let read = reader.read_line(&mut line).map_err(|e| match e.kind() {
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => "timed out waiting for banner".to_string(),
+            ErrorKind::InvalidData => "not an SSH server".to_string(),
+            _ => e.to_string(),
+        })?;
+        if read == 0 {
+            break;
+        }
+        if line.starts_with("SSH-") {
+            return Ok(line.trim_end().to_string());
+        }
+    }
+
+    Err("not an SSH server".to_string())
+}
+
+/// Parse a `major.minor` version such as `8.9`
+pub fn parse_version(input: &str) -> Option<(u32, u32)> {
+    let (major, minor) = input.split_once('.').unwrap_or((input, "0"));
+    let minor: String = minor.chars().take_while(char::is_ascii_digit).collect();
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// OpenSSH release advertised in a version string, `None` for other servers
+pub fn openssh_version(banner: &str) -> Option<(u32, u32)> {
+    let software = banner.splitn(3, '-').nth(2)?;
+    let version = software.strip_prefix("OpenSSH_")?;
+    parse_version(version.split_whitespace().next()?)
+}
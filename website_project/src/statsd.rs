@@ -0,0 +1,54 @@
+use std::{
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{checker::target_host, WebsiteStatus};
+
+/// Sends per-result metrics to a StatsD server, with Datadog style tags
+pub struct StatsdClient {
+    socket: UdpSocket,
+    server: SocketAddr,
+    /// Set after the first failed send, so a missing server is reported once
+    /// rather than for every result
+    warned: AtomicBool,
+}
+
+impl StatsdClient {
+    /// Resolve the server address and open the socket metrics are sent from
+    pub fn new(server: &str) -> Result<Self, String> {
+        let server = server
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or("failed to resolve StatsD server")?;
+        let local: SocketAddr = if server.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" }
+            .parse()
+            .expect("valid wildcard address");
+        let socket = UdpSocket::bind(local).map_err(|e| e.to_string())?;
+        Ok(StatsdClient { socket, server, warned: AtomicBool::new(false) })
+    }
+
+    /// Send the response time as a timer and the check as a counter, tagged
+    /// with the URL, host and outcome. Failures are logged, never fatal
+    pub fn send(&self, status: &WebsiteStatus) {
+        let tags = format!("url:{},host:{}", tag_value(&status.url), tag_value(&target_host(&status.url)));
+        let packet = format!(
+            "website.response_time:{}|ms|#{}\nwebsite.checks:1|c|#{},success:{}",
+            status.response_time.as_millis(),
+            tags,
+            tags,
+            status.success
+        );
+        if let Err(e) = self.socket.send_to(packet.as_bytes(), self.server)
+            && !self.warned.swap(true, Ordering::Relaxed)
+        {
+            eprintln!("Failed to send StatsD metrics to {}: {}", self.server, e);
+        }
+    }
+}
+
+/// Replace the characters StatsD uses as separators in a tag value
+fn tag_value(value: &str) -> String {
+    value.replace([',', '|', '#', '\n'], "_")
+}
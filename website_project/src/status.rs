@@ -0,0 +1,732 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+use chrono::DateTime;
+use reqwest::{
+    blocking::Request,
+    header::{HeaderMap, CACHE_CONTROL, DATE, ETAG, EXPIRES, SET_COOKIE, VARY},
+};
+
+use crate::{soft404::Fingerprint, TimeUnit, TimestampFormat, TimingBreakdown};
+
+/// Whether the values of header `name` are credentials to replace in
+/// recorded request details and printed configs: authorization and cookie
+/// headers, API keys and any `*-token` header
+pub(crate) fn is_sensitive_header(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    matches!(name.as_str(), "authorization" | "proxy-authorization" | "cookie" | "set-cookie" | "x-api-key")
+        || name.ends_with("-token")
+}
+
+/// Details of the HTTP request sent for a check
+#[derive(Debug, Clone)]
+pub struct RequestInfo {
+    pub method: String,
+    /// Header names and values in the order they were set
+    pub headers: Vec<(String, String)>,
+    pub body_length: usize,
+}
+
+impl RequestInfo {
+    /// Capture the details of a built request, optionally masking
+    /// credentials and cookies
+    pub(crate) fn from_request(request: &Request, redact: bool) -> Self {
+        RequestInfo {
+            method: request.method().to_string(),
+            headers: request.headers()
+                .iter()
+                .map(|(name, value)| {
+                    let value = if redact && is_sensitive_header(name.as_str()) {
+                        "[REDACTED]".to_string()
+                    } else {
+                        String::from_utf8_lossy(value.as_bytes()).into_owned()
+                    };
+                    (name.to_string(), value)
+                })
+                .collect(),
+            body_length: request.body()
+                .and_then(|body| body.as_bytes())
+                .map_or(0, <[u8]>::len),
+        }
+    }
+
+    pub fn to_json_string(&self) -> String {
+        format!(
+            "{{\"method\": \"{}\", \"headers\": {{{}}}, \"body_length\": {}}}",
+            escape_json(&self.method),
+            self.headers.iter()
+                .map(|(name, value)| format!("\"{}\": \"{}\"", escape_json(name), escape_json(value)))
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.body_length
+        )
+    }
+}
+
+/// Response time statistics over repeated requests to one URL
+#[derive(Debug, Clone)]
+pub struct LatencyStats {
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    pub p95: Duration,
+}
+
+/// Directives of an `Expect-CT` response header
+#[derive(Debug, Clone, Default)]
+pub struct ExpectCt {
+    /// Seconds the browser should remember the policy, `None` when missing
+    /// or not a number
+    pub max_age: Option<u64>,
+    /// Whether browsers should refuse connections without CT compliance,
+    /// rather than only report them
+    pub enforce: bool,
+    pub report_uri: Option<String>,
+}
+
+impl ExpectCt {
+    /// Parse a header value such as `max-age=86400, enforce, report-uri="https://..."`.
+    /// Unknown directives are ignored
+    pub fn parse(value: &str) -> Self {
+        let mut expect_ct = ExpectCt::default();
+        for directive in value.split(',') {
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name.trim(), Some(argument.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            match (name.to_ascii_lowercase().as_str(), argument) {
+                ("max-age", Some(seconds)) => expect_ct.max_age = seconds.parse().ok(),
+                ("enforce", _) => expect_ct.enforce = true,
+                ("report-uri", Some(uri)) => expect_ct.report_uri = Some(uri.to_string()),
+                _ => {}
+            }
+        }
+        expect_ct
+    }
+
+    pub fn to_json_string(&self) -> String {
+        format!(
+            "{{\"max_age\": {}, \"enforce\": {}, \"report_uri\": {}}}",
+            self.max_age.map_or("null".to_string(), |age| age.to_string()),
+            self.enforce,
+            self.report_uri.as_ref().map_or("null".to_string(), |uri| format!("\"{}\"", escape_json(uri)))
+        )
+    }
+}
+
+/// Caching policy of an HTTP response, from its `Cache-Control`, `Expires`,
+/// `ETag` and `Vary` headers
+#[derive(Debug, Clone, Default)]
+pub struct CachePolicy {
+    /// Whether shared caches such as a CDN may store and reuse the response
+    pub cacheable: bool,
+    /// Freshness lifetime from `max-age`, `s-maxage` or `Expires`
+    pub max_age_secs: Option<u64>,
+    /// Whether caches must check with the server before reusing it
+    /// (`must-revalidate`, `proxy-revalidate` or `no-cache`)
+    pub must_revalidate: bool,
+    pub private: bool,
+    pub no_store: bool,
+    /// Whether the response has an `ETag` to revalidate with
+    pub etag: bool,
+    pub vary: Option<String>,
+}
+
+impl CachePolicy {
+    /// Read the policy from response headers. `max-age` wins over
+    /// `s-maxage`, which wins over `Expires` counted from `Date`; unknown
+    /// directives are ignored
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name| headers.get(name).map(|value| String::from_utf8_lossy(value.as_bytes()).trim().to_string());
+        let mut policy = CachePolicy {
+            etag: headers.contains_key(ETAG),
+            vary: header(VARY),
+            ..CachePolicy::default()
+        };
+        let mut s_maxage = None;
+        for directive in header(CACHE_CONTROL).unwrap_or_default().split(',') {
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name.trim(), Some(argument.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            match (name.to_ascii_lowercase().as_str(), argument) {
+                ("max-age", Some(seconds)) => policy.max_age_secs = seconds.parse().ok(),
+                ("s-maxage", Some(seconds)) => s_maxage = seconds.parse().ok(),
+                ("must-revalidate" | "proxy-revalidate" | "no-cache", _) => policy.must_revalidate = true,
+                ("private", _) => policy.private = true,
+                ("no-store", _) => policy.no_store = true,
+                _ => {}
+            }
+        }
+        // An invalid date, such as the common `Expires: 0`, means already expired
+        let expires = header(EXPIRES).map(|expires| {
+            let date = |value: &str| DateTime::parse_from_rfc2822(value).ok();
+            let now = header(DATE).as_deref().and_then(date).map_or_else(chrono::Utc::now, |now| now.to_utc());
+            date(&expires).map_or(0, |expires| (expires.to_utc() - now).num_seconds().max(0) as u64)
+        });
+        policy.max_age_secs = policy.max_age_secs.or(s_maxage).or(expires);
+        policy.cacheable = !policy.no_store
+            && !policy.private
+            && policy.vary.as_deref() != Some("*")
+            && (policy.max_age_secs.is_some_and(|age| age > 0) || policy.etag);
+        policy
+    }
+
+    pub fn to_json_string(&self) -> String {
+        format!(
+            "{{\"cacheable\": {}, \"max_age_secs\": {}, \"must_revalidate\": {}, \"private\": {}, \"no_store\": {}, \"etag\": {}, \"vary\": {}}}",
+            self.cacheable,
+            self.max_age_secs.map_or("null".to_string(), |age| age.to_string()),
+            self.must_revalidate,
+            self.private,
+            self.no_store,
+            self.etag,
+            self.vary.as_ref().map_or("null".to_string(), |vary| format!("\"{}\"", escape_json(vary)))
+        )
+    }
+}
+
+/// Security attributes of a cookie set by a `Set-Cookie` response header
+#[derive(Debug, Clone, Default)]
+pub struct CookieSecurity {
+    pub name: String,
+    pub secure: bool,
+    pub httponly: bool,
+    /// `SameSite` value as sent, such as `Lax` or `Strict`
+    pub samesite: Option<String>,
+    pub domain: Option<String>,
+    pub path: Option<String>,
+}
+
+impl CookieSecurity {
+    /// Parse a header value such as `id=a3fWa; Secure; HttpOnly; SameSite=Lax`.
+    /// Attribute names are case-insensitive and unknown ones are ignored
+    pub fn parse(value: &str) -> Self {
+        let mut parts = value.split(';');
+        let name = parts.next().unwrap_or_default().split('=').next().unwrap_or_default().trim();
+        let mut cookie = CookieSecurity { name: name.to_string(), ..CookieSecurity::default() };
+        for attribute in parts {
+            let (name, argument) = match attribute.split_once('=') {
+                Some((name, argument)) => (name.trim(), Some(argument.trim().to_string())),
+                None => (attribute.trim(), None),
+            };
+            match name.to_ascii_lowercase().as_str() {
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.httponly = true,
+                "samesite" => cookie.samesite = argument,
+                "domain" => cookie.domain = argument,
+                "path" => cookie.path = argument,
+                _ => {}
+            }
+        }
+        cookie
+    }
+
+    /// Every cookie set by a response, in header order
+    pub fn from_headers(headers: &HeaderMap) -> Vec<Self> {
+        headers.get_all(SET_COOKIE)
+            .iter()
+            .map(|value| CookieSecurity::parse(&String::from_utf8_lossy(value.as_bytes())))
+            .collect()
+    }
+
+    pub fn to_json_string(&self) -> String {
+        let text = |value: &Option<String>| value.as_ref().map_or("null".to_string(), |value| format!("\"{}\"", escape_json(value)));
+        format!(
+            "{{\"name\": \"{}\", \"secure\": {}, \"httponly\": {}, \"samesite\": {}, \"domain\": {}, \"path\": {}}}",
+            escape_json(&self.name),
+            self.secure,
+            self.httponly,
+            text(&self.samesite),
+            text(&self.domain),
+            text(&self.path)
+        )
+    }
+}
+
+/// Outcome of one path checked by a multi-path host probe
+#[derive(Debug, Clone)]
+pub struct ProbeResult {
+    pub path: String,
+    pub action_status: Result<u16, String>,
+    pub response_time: Duration,
+    pub success: bool,
+}
+
+impl ProbeResult {
+    pub fn to_json_string(&self, unit: TimeUnit) -> String {
+        let status = match &self.action_status {
+            Ok(code) => code.to_string(),
+            Err(e) => format!("\"{}\"", escape_json(e)),
+        };
+        format!(
+            "{{\"path\": \"{}\", \"status\": {}, \"response_time_{}\": {}, \"success\": {}}}",
+            escape_json(&self.path),
+            status,
+            unit.suffix(),
+            unit.format(self.response_time),
+            self.success
+        )
+    }
+}
+
+/// Outcome of checking a single target
+#[derive(Debug, Clone)]
+pub struct WebsiteStatus {
+    pub url: String,
+    /// Status code on success, error message otherwise. ICMP replies, SSH
+    /// banners and DNS checks are reported as 0, SMTP checks as the 220
+    /// greeting
+    pub action_status: Result<u16, String>,
+    pub response_time: Duration,
+    pub timestamp: SystemTime,
+    /// Whether the outcome counts as healthy under the configured success criteria
+    pub success: bool,
+    /// Negotiated TLS version, only probed for https URLs with TLS info enabled
+    pub tls_version: Option<Result<String, String>>,
+    /// Greeting line of an SMTP server
+    pub smtp_banner: Option<String>,
+    /// Version string sent by an SSH server
+    pub ssh_version: Option<String>,
+    /// Request details, recorded for HTTP checks when enabled
+    pub request: Option<RequestInfo>,
+    /// Addresses returned by each resolver in a DNS propagation check
+    pub dns_results: Option<HashMap<String, Vec<String>>>,
+    /// Whether the resolvers disagreed
+    pub dns_inconsistent: bool,
+    /// Custom DNS server that resolved the host, recorded when verbose
+    pub dns_server: Option<String>,
+    /// Per-phase timings, recorded for HTTP checks when enabled
+    pub timing: Option<TimingBreakdown>,
+    /// Whole days until the certificate expires, negative once expired
+    pub cert_expires_in_days: Option<i64>,
+    /// Certificate expiry date as reported by OpenSSL
+    pub cert_not_after: Option<String>,
+    /// Number of requests sent to the URL
+    pub sample_count: u32,
+    /// Statistics over the successful samples when more than one was taken
+    pub latency: Option<LatencyStats>,
+    /// HTTP requests sent, including retries
+    pub requests_sent: u32,
+    /// Requests after which the connection could not be kept alive
+    pub connections_closed: u32,
+    /// Failed attempts that were retried
+    pub retries_used: u32,
+    /// Value of the configured version header
+    pub version_header_value: Option<String>,
+    /// Whether the version header satisfied the configured requirement
+    pub version_requirement_met: Option<bool>,
+    /// `Content-Length` reported for a HEAD request when tracking sizes
+    pub content_length: Option<u64>,
+    /// Change in bytes against the size baseline
+    pub size_delta: Option<i64>,
+    /// Whether the size changed by more than the configured threshold
+    pub size_changed: bool,
+    /// Whether the response came faster than the configured minimum
+    pub too_fast: bool,
+    /// Whether the response had no body, recorded when the body is checked
+    pub body_empty: bool,
+    /// Per-path results when a host was checked against several probe paths
+    pub probes: Option<Vec<ProbeResult>>,
+    /// Parsed `Expect-CT` header, recorded for HTTP checks when enabled
+    pub expect_ct: Option<ExpectCt>,
+    /// `Content-Encoding` of the response, `identity` for an uncompressed
+    /// one when compression is disabled
+    pub content_encoding: Option<String>,
+    /// CSP `frame-ancestors` directive or `X-Frame-Options` value of the
+    /// response, recorded for HTTP checks when enabled
+    pub framing_protection: Option<String>,
+    /// Caching headers of the response, recorded for HTTP checks when enabled
+    pub cache_policy: Option<CachePolicy>,
+    /// Seconds since the response's `Last-Modified`, or `Date` without it,
+    /// recorded when content age is checked
+    pub content_age_secs: Option<u64>,
+    /// Cookies set by the response, recorded for HTTP checks when enabled
+    pub cookies: Vec<CookieSecurity>,
+    /// Result for the URL with its trailing slash toggled
+    pub trailing_slash_variant: Option<ProbeResult>,
+    /// Outcome of the HEAD sent before the GET with a HEAD preflight
+    pub head_preflight: Option<ProbeResult>,
+    /// Whether the variant answered with a different status
+    pub trailing_slash_mismatch: bool,
+    /// File the response body was saved to, or why saving failed
+    pub body_file: Option<Result<String, String>>,
+    /// Start of the body of a response with a failing status, when captured
+    pub error_body: Option<String>,
+    /// `<a href>` links on the page, counted for HTML responses when link
+    /// count limits are set
+    pub link_count: Option<u32>,
+    /// `<img>` elements without alt text and `<svg role="img">` elements
+    /// without an accessible name, counted for HTML responses when asked
+    pub images_missing_alt: Option<u32>,
+    /// `<title>` of HTML pages, recorded when a title pattern is set; empty
+    /// when the page has none
+    pub page_title: Option<String>,
+    /// `<link rel="canonical">` target of HTML pages, resolved against the
+    /// page URL, recorded when canonical checks are enabled
+    pub canonical_url: Option<String>,
+    /// Whether the canonical URL differs from the URL fetched after redirects
+    pub canonical_mismatch: bool,
+    /// Open Graph tags an HTML page lacks, when they are checked
+    pub missing_og_tags: Vec<String>,
+    /// Cross-origin resources of an HTML page loaded without Subresource
+    /// Integrity, when they are checked
+    pub missing_sri: Vec<String>,
+    /// `og:image` of an HTML page, resolved against the page URL
+    pub og_image: Option<String>,
+    /// `content` of the viewport meta tag of HTML pages, when it is checked
+    pub viewport_meta: Option<String>,
+    /// Whether the viewport meta tag suits mobile devices. Pages without
+    /// the tag fail, so a checked page has `viewport_meta` or fails, and
+    /// unchecked ones, e.g. responses that are not HTML, pass
+    pub viewport_meta_ok: bool,
+    /// Schema types declared by JSON-LD and microdata, when they are checked
+    pub structured_data_types: Vec<String>,
+    /// Whether a JSON-LD block on the page is not valid JSON
+    pub structured_data_invalid: bool,
+    /// JSON Schema violations of the body, the first few when there are many
+    pub schema_errors: Vec<String>,
+    /// Length and hash of the body, taken when detecting soft 404s
+    pub body_fingerprint: Option<Fingerprint>,
+    /// Why the page looks like its host's answer for a missing path
+    pub possible_soft_404: Option<String>,
+    /// Index of the worker thread that ran the check
+    pub worker: usize,
+}
+
+impl WebsiteStatus {
+    /// A result for `url` that has not been checked yet
+    pub(crate) fn new(url: &str) -> Self {
+        WebsiteStatus {
+            url: url.to_string(),
+            action_status: Err("not checked".to_string()),
+            response_time: Duration::default(),
+            timestamp: SystemTime::now(),
+            success: false,
+            tls_version: None,
+            smtp_banner: None,
+            ssh_version: None,
+            request: None,
+            dns_results: None,
+            dns_inconsistent: false,
+            dns_server: None,
+            timing: None,
+            cert_expires_in_days: None,
+            cert_not_after: None,
+            sample_count: 1,
+            latency: None,
+            requests_sent: 0,
+            connections_closed: 0,
+            retries_used: 0,
+            version_header_value: None,
+            version_requirement_met: None,
+            content_length: None,
+            size_delta: None,
+            size_changed: false,
+            too_fast: false,
+            body_empty: false,
+            probes: None,
+            expect_ct: None,
+            content_encoding: None,
+            framing_protection: None,
+            cache_policy: None,
+            cookies: Vec::new(),
+            content_age_secs: None,
+            trailing_slash_variant: None,
+            head_preflight: None,
+            trailing_slash_mismatch: false,
+            body_file: None,
+            error_body: None,
+            link_count: None,
+            images_missing_alt: None,
+            page_title: None,
+            canonical_url: None,
+            canonical_mismatch: false,
+            missing_og_tags: Vec::new(),
+            missing_sri: Vec::new(),
+            og_image: None,
+            viewport_meta: None,
+            viewport_meta_ok: true,
+            structured_data_types: Vec::new(),
+            structured_data_invalid: false,
+            schema_errors: Vec::new(),
+            body_fingerprint: None,
+            possible_soft_404: None,
+            worker: 0,
+        }
+    }
+
+    pub fn to_json_string(&self, timestamps: TimestampFormat, unit: TimeUnit) -> String {
+        format!("{{\n    {}\n}}", self.json_fields(timestamps, unit).join(",\n    "))
+    }
+
+    /// The result as a single-line JSON object, led by the ID of the run
+    /// that produced it
+    pub fn to_json_line(&self, timestamps: TimestampFormat, unit: TimeUnit, run_id: &str) -> String {
+        let mut fields = vec![format!("\"run_id\": \"{}\"", escape_json(run_id))];
+        fields.extend(self.json_fields(timestamps, unit));
+        format!("{{{}}}", fields.join(", "))
+    }
+
+    fn json_fields(&self, timestamps: TimestampFormat, unit: TimeUnit) -> Vec<String> {
+        let status = match &self.action_status {
+            Ok(code) => code.to_string(),
+            Err(e) => format!("\"{}\"", escape_json(e)),
+        };
+        
+        let timestamp = timestamps.to_json(self.timestamp);
+            
+        let mut fields = vec![
+            format!("\"url\": \"{}\"", escape_json(&self.url)),
+            format!("\"status\": {}", status),
+            format!("\"response_time_{}\": {}", unit.suffix(), unit.format(self.response_time)),
+            format!("\"timestamp\": {}", timestamp),
+            format!("\"success\": {}", self.success),
+        ];
+
+        match &self.tls_version {
+            Some(Ok(version)) => fields.push(format!("\"tls_version\": \"{}\"", version)),
+            Some(Err(e)) => {
+                fields.push("\"tls_version\": null".to_string());
+                fields.push(format!("\"tls_error\": \"{}\"", escape_json(e)));
+            }
+            None => {}
+        }
+
+        if let Some(banner) = &self.smtp_banner {
+            fields.push(format!("\"smtp_banner\": \"{}\"", escape_json(banner)));
+        }
+        if let Some(version) = &self.ssh_version {
+            fields.push(format!("\"ssh_version\": \"{}\"", escape_json(version)));
+        }
+        if let Some(request) = &self.request {
+            fields.push(format!("\"request\": {}", request.to_json_string()));
+        }
+        if let Some(dns_results) = &self.dns_results {
+            let mut resolvers: Vec<_> = dns_results.iter().collect();
+            resolvers.sort();
+            fields.push(format!(
+                "\"dns_results\": {{{}}}",
+                resolvers.iter()
+                    .map(|(resolver, addresses)| format!(
+                        "\"{}\": [{}]",
+                        escape_json(resolver),
+                        addresses.iter()
+                            .map(|a| format!("\"{}\"", escape_json(a)))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+            fields.push(format!("\"dns_inconsistent\": {}", self.dns_inconsistent));
+        }
+        if let Some(server) = &self.dns_server {
+            fields.push(format!("\"dns_server\": \"{}\"", escape_json(server)));
+        }
+        if let Some(timing) = &self.timing {
+            fields.push(format!("\"timing\": {}", timing.to_json_string(unit)));
+        }
+        if let Some(days) = self.cert_expires_in_days {
+            fields.push(format!("\"cert_expires_in_days\": {}", days));
+        }
+        if let Some(not_after) = &self.cert_not_after {
+            fields.push(format!("\"cert_not_after\": \"{}\"", escape_json(not_after)));
+        }
+        if self.sample_count > 1 {
+            fields.push(format!("\"sample_count\": {}", self.sample_count));
+        }
+        if self.retries_used > 0 {
+            fields.push(format!("\"retries\": {}", self.retries_used));
+        }
+        if let Some(version) = &self.version_header_value {
+            fields.push(format!("\"version_header_value\": \"{}\"", escape_json(version)));
+        }
+        if let Some(met) = self.version_requirement_met {
+            fields.push(format!("\"version_requirement_met\": {}", met));
+        }
+        if let Some(length) = self.content_length {
+            fields.push(format!("\"content_length\": {}", length));
+        }
+        if let Some(delta) = self.size_delta {
+            fields.push(format!("\"size_delta\": {}", delta));
+            fields.push(format!("\"size_changed\": {}", self.size_changed));
+        }
+        if self.too_fast {
+            fields.push("\"too_fast\": true".to_string());
+        }
+        if self.body_empty {
+            fields.push("\"body_empty\": true".to_string());
+        }
+        if let Some(probes) = &self.probes {
+            fields.push(format!(
+                "\"probes\": [{}]",
+                probes.iter()
+                    .map(|probe| probe.to_json_string(unit))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        if let Some(expect_ct) = &self.expect_ct {
+            fields.push(format!("\"expect_ct\": {}", expect_ct.to_json_string()));
+        }
+        if let Some(age) = self.content_age_secs {
+            fields.push(format!("\"content_age_secs\": {}", age));
+        }
+        if !self.cookies.is_empty() {
+            fields.push(format!(
+                "\"cookies\": [{}]",
+                self.cookies.iter()
+                    .map(CookieSecurity::to_json_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        if let Some(value) = &self.framing_protection {
+            fields.push(format!("\"framing_protection\": \"{}\"", escape_json(value)));
+        }
+        if let Some(policy) = &self.cache_policy {
+            fields.push(format!("\"cache_policy\": {}", policy.to_json_string()));
+        }
+        if let Some(encoding) = &self.content_encoding {
+            fields.push(format!("\"content_encoding\": \"{}\"", escape_json(encoding)));
+        }
+        if let Some(preflight) = &self.head_preflight {
+            fields.push(format!("\"head_preflight\": {}", preflight.to_json_string(unit)));
+        }
+        if let Some(variant) = &self.trailing_slash_variant {
+            fields.push(format!("\"trailing_slash_variant\": {}", variant.to_json_string(unit)));
+            fields.push(format!("\"trailing_slash_mismatch\": {}", self.trailing_slash_mismatch));
+        }
+        if let Some(count) = self.link_count {
+            fields.push(format!("\"link_count\": {}", count));
+        }
+        if let Some(count) = self.images_missing_alt {
+            fields.push(format!("\"images_missing_alt\": {}", count));
+        }
+        if let Some(title) = &self.page_title {
+            fields.push(format!("\"page_title\": \"{}\"", escape_json(title)));
+        }
+        if let Some(canonical) = &self.canonical_url {
+            fields.push(format!("\"canonical_url\": \"{}\"", escape_json(canonical)));
+            fields.push(format!("\"canonical_mismatch\": {}", self.canonical_mismatch));
+        }
+        if !self.missing_og_tags.is_empty() {
+            fields.push(format!(
+                "\"missing_og_tags\": [{}]",
+                self.missing_og_tags.iter()
+                    .map(|tag| format!("\"{}\"", escape_json(tag)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        if !self.missing_sri.is_empty() {
+            fields.push(format!(
+                "\"missing_sri\": [{}]",
+                self.missing_sri.iter()
+                    .map(|url| format!("\"{}\"", escape_json(url)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        if let Some(image) = &self.og_image {
+            fields.push(format!("\"og_image\": \"{}\"", escape_json(image)));
+        }
+        if self.viewport_meta.is_some() || !self.viewport_meta_ok {
+            let content = self.viewport_meta.as_ref().map_or("null".to_string(), |c| format!("\"{}\"", escape_json(c)));
+            fields.push(format!("\"viewport_meta\": {}", content));
+            fields.push(format!("\"viewport_meta_ok\": {}", self.viewport_meta_ok));
+        }
+        if !self.structured_data_types.is_empty() || self.structured_data_invalid {
+            fields.push(format!(
+                "\"structured_data_types\": [{}]",
+                self.structured_data_types.iter()
+                    .map(|name| format!("\"{}\"", escape_json(name)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+            fields.push(format!("\"structured_data_invalid\": {}", self.structured_data_invalid));
+        }
+        if !self.schema_errors.is_empty() {
+            fields.push(format!(
+                "\"schema_errors\": [{}]",
+                self.schema_errors.iter()
+                    .map(|error| format!("\"{}\"", escape_json(error)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        if let Some(reason) = &self.possible_soft_404 {
+            fields.push(format!("\"possible_soft_404\": \"{}\"", escape_json(reason)));
+        }
+        match &self.body_file {
+            Some(Ok(path)) => fields.push(format!("\"body_file\": \"{}\"", escape_json(path))),
+            Some(Err(e)) => fields.push(format!("\"body_file_error\": \"{}\"", escape_json(e))),
+            None => {}
+        }
+        if let Some(body) = &self.error_body {
+            fields.push(format!("\"error_body\": \"{}\"", escape_json(body)));
+        }
+
+        if let Some(latency) = &self.latency {
+            for (name, time) in [("min", latency.min), ("max", latency.max), ("mean", latency.mean), ("p95", latency.p95)] {
+                fields.push(format!("\"response_time_{}_{}\": {}", name, unit.suffix(), unit.format(time)));
+            }
+        }
+
+        fields
+    }
+
+    /// Human-readable outcome for the console
+    pub fn describe(&self) -> String {
+        match &self.action_status {
+            Ok(_) if self.url.starts_with("icmp://") => "ICMP echo reply".to_string(),
+            Ok(code) if self.url.starts_with("smtp://") => format!("SMTP {}", code),
+            Ok(_) if self.url.starts_with("ssh://") => self.ssh_version.clone().unwrap_or_default(),
+            Ok(_) if self.url.starts_with("dns://") => "DNS consistent".to_string(),
+            Ok(_) if self.url.starts_with("ws://") || self.url.starts_with("wss://") => {
+                "WebSocket handshake OK".to_string()
+            }
+            Ok(code) => format!("HTTP {}", code),
+            Err(e) => format!("ERROR: {}", e),
+        }
+    }
+}
+
+/// Escape a string for embedding in a JSON string literal
+pub(crate) fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_sensitive_header;
+
+    #[test]
+    fn recognizes_credential_headers() {
+        for name in ["Authorization", "proxy-authorization", "Cookie", "Set-Cookie", "X-API-Key", "X-Auth-Token"] {
+            assert!(is_sensitive_header(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn ignores_other_headers() {
+        for name in ["Accept", "User-Agent", "X-Tokenizer", "Content-Type"] {
+            assert!(!is_sensitive_header(name), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use crate::{status::escape_json, TimeUnit, WebsiteStatus};
+
+/// Nearest-rank percentile of an ascending list of durations
+pub(crate) fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::default();
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Aggregate outcome of a scan
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub total: usize,
+    pub succeeded: usize,
+    /// URLs of every unsuccessful check, in result order
+    pub failed: Vec<String>,
+    /// Response time percentiles over every check
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+impl Summary {
+    pub fn from_results(results: &[WebsiteStatus]) -> Self {
+        let mut times: Vec<Duration> = results.iter().map(|r| r.response_time).collect();
+        times.sort();
+        Summary {
+            total: results.len(),
+            succeeded: results.iter().filter(|r| r.success).count(),
+            failed: results.iter()
+                .filter(|r| !r.success)
+                .map(|r| r.url.clone())
+                .collect(),
+            p50: percentile(&times, 50.0),
+            p95: percentile(&times, 95.0),
+            p99: percentile(&times, 99.0),
+        }
+    }
+
+    pub fn to_json_string(&self, unit: TimeUnit) -> String {
+        let suffix = unit.suffix();
+        format!(
+            r#"{{
+        "total": {},
+        "succeeded": {},
+        "response_time_p50_{suffix}": {},
+        "response_time_p95_{suffix}": {},
+        "response_time_p99_{suffix}": {},
+        "failed": [{}]
+    }}"#,
+            self.total,
+            self.succeeded,
+            unit.format(self.p50),
+            unit.format(self.p95),
+            unit.format(self.p99),
+            self.failed.iter()
+                .map(|url| format!("\"{}\"", escape_json(url)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
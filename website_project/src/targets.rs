@@ -0,0 +1,187 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+    time::Duration,
+};
+
+use reqwest::Url;
+
+use serde_json::Value;
+
+use crate::{bodies::fnv1a, JsonSchema};
+
+/// A target read from a URL list
+pub struct Entry {
+    /// 1-based line number
+    pub line: usize,
+    pub url: String,
+    /// Check period from an `interval=S` annotation after the URL
+    pub interval: Option<Duration>,
+    /// Retry count from a `retries=N` annotation, replacing `--retries`
+    pub retries: Option<u32>,
+    /// Request body from a `body=` annotation
+    pub body: Option<Vec<u8>>,
+}
+
+/// Read a URL list, one target per line, optionally followed by
+/// whitespace-separated `key=value` annotations. A `body=` annotation comes
+/// last and runs to the end of the line, so inline payloads may contain
+/// spaces; `body=@FILE` reads it from FILE, relative to the list. Blank
+/// lines and `#` comments are skipped
+pub fn read_file(path: &Path) -> io::Result<Vec<Entry>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim_start();
+        let Some(url) = line.split_whitespace().next().filter(|url| !url.starts_with('#')) else {
+            continue;
+        };
+        let mut entry = Entry { line: index + 1, url: url.to_string(), interval: None, retries: None, body: None };
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", index + 1, e));
+        let (annotations, body) = split_body(&line[url.len()..]);
+        for annotation in annotations.split_whitespace() {
+            parse_annotation(&mut entry, annotation).map_err(invalid)?;
+        }
+        if let Some(body) = body {
+            entry.body = Some(read_body(path, body).map_err(invalid)?);
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Read URL specs given as JSON: an array, or one per line. A spec is a
+/// URL string or an object with a `url` string; its other members are left
+/// to `schema`, which every spec must satisfy when given. Returns the URLs,
+/// or every problem found, each naming the 0-based index of its spec
+pub fn read_json_specs(input: &str, schema: Option<&JsonSchema>) -> Result<Vec<String>, Vec<String>> {
+    let specs: Vec<Result<Value, String>> = if input.trim_start().starts_with('[') {
+        match serde_json::from_str::<Vec<Value>>(input) {
+            Ok(specs) => specs.into_iter().map(Ok).collect(),
+            Err(e) => return Err(vec![format!("invalid JSON: {}", e)]),
+        }
+    } else {
+        input.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(|e| format!("invalid JSON: {}", e)))
+            .collect()
+    };
+
+    let mut urls = Vec::new();
+    let mut errors = Vec::new();
+    for (index, spec) in specs.into_iter().enumerate() {
+        let spec = match spec {
+            Ok(spec) => spec,
+            Err(e) => {
+                errors.push(format!("spec {}: {}", index, e));
+                continue;
+            }
+        };
+        if let Some(schema) = schema {
+            let violations = schema.value_errors(&spec);
+            if !violations.is_empty() {
+                errors.extend(violations.iter().map(|violation| format!("spec {}: {}", index, violation)));
+                continue;
+            }
+        }
+        match spec.as_str().or_else(|| spec.get("url")?.as_str()) {
+            Some(url) => urls.push(url.to_string()),
+            None => errors.push(format!("spec {}: expected a URL string or an object with a url string", index)),
+        }
+    }
+    if errors.is_empty() { Ok(urls) } else { Err(errors) }
+}
+
+/// Split off a trailing `body=` annotation, returning the other annotations
+/// and the body's value
+fn split_body(annotations: &str) -> (&str, Option<&str>) {
+    let start = annotations.match_indices("body=")
+        .map(|(index, _)| index)
+        .find(|index| annotations[..*index].ends_with(char::is_whitespace));
+    match start {
+        Some(index) => (&annotations[..index], Some(annotations[index + "body=".len()..].trim())),
+        None => (annotations, None),
+    }
+}
+
+fn read_body(list: &Path, body: &str) -> Result<Vec<u8>, String> {
+    if body.is_empty() {
+        return Err("empty body".to_string());
+    }
+    match body.strip_prefix('@') {
+        Some(file) => {
+            let file = list.parent().unwrap_or(Path::new("")).join(file);
+            std::fs::read(&file).map_err(|e| format!("body file {}: {}", file.display(), e))
+        }
+        None => Ok(body.as_bytes().to_vec()),
+    }
+}
+
+fn parse_annotation(entry: &mut Entry, annotation: &str) -> Result<(), String> {
+    match annotation.split_once('=') {
+        Some(("interval", secs)) => {
+            let secs: u64 = secs.parse().ok().filter(|secs| *secs > 0).ok_or("invalid interval")?;
+            entry.interval = Some(Duration::from_secs(secs));
+            Ok(())
+        }
+        Some(("retries", retries)) => {
+            entry.retries = Some(retries.parse().map_err(|_| "invalid retries")?);
+            Ok(())
+        }
+        _ => Err(format!("unknown annotation {}", annotation)),
+    }
+}
+
+/// One of `total` disjoint parts of a URL list, chosen by a hash of each URL
+/// so every machine running the same list picks the same URLs
+#[derive(Debug, Clone, Copy)]
+pub struct Shard {
+    /// 0-based, below `total`
+    pub index: u32,
+    pub total: u32,
+}
+
+impl Shard {
+    /// Parse `INDEX/TOTAL`
+    pub fn parse(s: &str) -> Result<Shard, String> {
+        let (index, total) = s.split_once('/').ok_or("expected INDEX/TOTAL, e.g. 0/4")?;
+        let index: u32 = index.parse().map_err(|_| format!("invalid shard index {}", index))?;
+        let total: u32 = total.parse().ok().filter(|total| *total > 0).ok_or(format!("invalid shard total {}", total))?;
+        if index >= total {
+            return Err(format!("shard index {} must be below the total {}, shards count from 0", index, total));
+        }
+        Ok(Shard { index, total })
+    }
+
+    pub fn contains(&self, url: &str) -> bool {
+        fnv1a(url.as_bytes()) % self.total == self.index
+    }
+}
+
+/// Check that a target can be requested: a known scheme with a host, and a
+/// port for the TCP checks that have no default one
+pub fn validate(target: &str) -> Result<(), String> {
+    let (scheme, rest) = target.split_once("://").ok_or("missing scheme, expected e.g. https://")?;
+    match scheme {
+        "http" | "https" | "ws" | "wss" => {
+            let url = Url::parse(target).map_err(|e| e.to_string())?;
+            if url.host_str().is_none_or(str::is_empty) {
+                return Err("missing host".to_string());
+            }
+            Ok(())
+        }
+        "icmp" | "dns" => {
+            if rest.is_empty() || rest.contains('/') {
+                return Err(format!("expected {}://HOST", scheme));
+            }
+            Ok(())
+        }
+        "smtp" | "ssh" => match rest.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(()),
+            _ => Err(format!("expected {}://HOST:PORT", scheme)),
+        },
+        other => Err(format!("unsupported scheme: {}", other)),
+    }
+}
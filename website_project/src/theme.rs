@@ -0,0 +1,48 @@
+//! Colors of the live console output, with palettes for colorblind users
+//! and a monochrome one for terminals without colors.
+
+use std::env;
+
+/// How the outcome of each check is marked on the console
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorTheme {
+    /// Green for success, red for failure
+    #[default]
+    Default,
+    /// Blue for success, orange for failure, which most colorblind users
+    /// tell apart
+    Colorblind,
+    /// No colors, a ✓ or ✗ before each outcome instead
+    Mono,
+}
+
+impl ColorTheme {
+    /// Parse `default`, `colorblind` or `mono`
+    pub fn parse(name: &str) -> Option<ColorTheme> {
+        match name {
+            "default" => Some(ColorTheme::Default),
+            "colorblind" => Some(ColorTheme::Colorblind),
+            "mono" => Some(ColorTheme::Mono),
+            _ => None,
+        }
+    }
+
+    /// Mark up the outcome of a check. `colors` is false when the console
+    /// is not a terminal or colors are turned off, which leaves the text
+    /// plain except for the symbols of the monochrome theme
+    pub fn paint(self, success: bool, text: &str, colors: bool) -> String {
+        let code = match self {
+            ColorTheme::Mono => return format!("{} {}", if success { "✓" } else { "✗" }, text),
+            _ if !colors => return text.to_string(),
+            ColorTheme::Default => if success { "32" } else { "31" },
+            ColorTheme::Colorblind => if success { "34" } else { "38;5;208" },
+        };
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    }
+}
+
+/// Whether console output to a stream is colored: only terminals get
+/// colors, and never when `NO_COLOR` is set to anything but an empty string
+pub fn colors_enabled(terminal: bool) -> bool {
+    terminal && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
@@ -0,0 +1,103 @@
+use std::{
+    sync::{Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Token bucket shared by all workers, admitting at most `rate` callers per
+/// second. It holds a single token, so admissions are spread out evenly
+/// instead of arriving in a burst at the start of each second
+#[derive(Debug)]
+pub struct TokenBucket {
+    interval: Duration,
+    /// Earliest time the next caller may proceed
+    next: Mutex<Instant>,
+}
+
+impl TokenBucket {
+    pub fn new(rate: f64) -> Self {
+        TokenBucket {
+            interval: Duration::from_secs_f64(1.0 / rate),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Block until the caller may proceed. Slots are handed out in call
+    /// order, so waiting callers never starve each other
+    pub fn acquire(&self) {
+        let wait = {
+            let mut next = self.next.lock().unwrap();
+            let now = Instant::now();
+            let slot = (*next).max(now);
+            *next = slot + self.interval;
+            slot - now
+        };
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+}
+
+/// Concurrency limit that grows linearly from 1 to `max` over `ramp`,
+/// so a scan starts gently instead of at full concurrency
+pub struct RampGate {
+    start: Instant,
+    ramp: Duration,
+    max: usize,
+    running: Mutex<usize>,
+    released: Condvar,
+}
+
+impl RampGate {
+    pub fn new(ramp: Duration, max: usize) -> Self {
+        RampGate {
+            start: Instant::now(),
+            ramp,
+            max: max.max(1),
+            running: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Checks allowed to run at once right now, and how long until one more is
+    fn allowed(&self) -> (usize, Option<Duration>) {
+        let elapsed = self.start.elapsed();
+        if elapsed >= self.ramp || self.max == 1 {
+            return (self.max, None);
+        }
+        let steps = (self.max - 1) as f64;
+        let allowed = 1 + (elapsed.as_secs_f64() / self.ramp.as_secs_f64() * steps) as usize;
+        let next = self.ramp.mul_f64(allowed as f64 / steps);
+        (allowed, Some(next.saturating_sub(elapsed)))
+    }
+
+    /// Wait until the current limit leaves room for another check, then take
+    /// a slot that is given back when the permit drops
+    pub fn acquire(&self) -> RampPermit<'_> {
+        let mut running = self.running.lock().unwrap();
+        loop {
+            let (allowed, next) = self.allowed();
+            if *running < allowed {
+                break;
+            }
+            running = match next {
+                Some(wait) => self.released.wait_timeout(running, wait).unwrap().0,
+                None => self.released.wait(running).unwrap(),
+            };
+        }
+        *running += 1;
+        RampPermit { gate: self }
+    }
+}
+
+/// A running check's share of the ramp-up limit
+pub struct RampPermit<'a> {
+    gate: &'a RampGate,
+}
+
+impl Drop for RampPermit<'_> {
+    fn drop(&mut self) {
+        *self.gate.running.lock().unwrap() -= 1;
+        self.gate.released.notify_one();
+    }
+}
@@ -0,0 +1,90 @@
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use chrono_tz::Tz;
+
+/// How timestamps are written to result files
+#[derive(Debug, Clone, Copy, Default)]
+pub enum TimestampFormat {
+    /// Seconds since the Unix epoch, as a number
+    #[default]
+    Unix,
+    /// An RFC 3339 string in the given time zone
+    Rfc3339(Tz),
+}
+
+impl TimestampFormat {
+    /// Parse a format name; `timezone` only affects `rfc3339`
+    pub fn parse(name: &str, timezone: Tz) -> Option<TimestampFormat> {
+        match name {
+            "unix" => Some(TimestampFormat::Unix),
+            "rfc3339" => Some(TimestampFormat::Rfc3339(timezone)),
+            _ => None,
+        }
+    }
+
+    /// The timestamp as plain text
+    pub fn format(self, time: SystemTime) -> String {
+        match self {
+            TimestampFormat::Unix => time
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or(Duration::from_secs(0))
+                .as_secs()
+                .to_string(),
+            TimestampFormat::Rfc3339(timezone) => DateTime::<Utc>::from(time)
+                .with_timezone(&timezone)
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+
+    /// The timestamp as a JSON value
+    pub fn to_json(self, time: SystemTime) -> String {
+        match self {
+            TimestampFormat::Unix => self.format(time),
+            TimestampFormat::Rfc3339(_) => format!("\"{}\"", self.format(time)),
+        }
+    }
+}
+
+/// Unit of durations, such as response times, in result files. Field names
+/// end in it, e.g. `response_time_ms`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeUnit {
+    /// Whole milliseconds
+    #[default]
+    Millis,
+    /// Whole microseconds, for sub-millisecond local checks
+    Micros,
+    /// Seconds with three decimals
+    Secs,
+}
+
+impl TimeUnit {
+    /// Parse `ms`, `us` or `s`
+    pub fn parse(name: &str) -> Option<TimeUnit> {
+        match name {
+            "ms" => Some(TimeUnit::Millis),
+            "us" => Some(TimeUnit::Micros),
+            "s" => Some(TimeUnit::Secs),
+            _ => None,
+        }
+    }
+
+    /// Suffix of field names holding durations
+    pub fn suffix(self) -> &'static str {
+        match self {
+            TimeUnit::Millis => "ms",
+            TimeUnit::Micros => "us",
+            TimeUnit::Secs => "s",
+        }
+    }
+
+    /// The duration as a number in this unit
+    pub fn format(self, duration: Duration) -> String {
+        match self {
+            TimeUnit::Millis => duration.as_millis().to_string(),
+            TimeUnit::Micros => duration.as_micros().to_string(),
+            TimeUnit::Secs => format!("{:.3}", duration.as_secs_f64()),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    net::{TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use reqwest::Url;
+
+use crate::tls;
+use crate::TimeUnit;
+
+/// Time spent in each phase of a request.
+///
+/// reqwest does not report connection phases, so `dns`, `connect` and
+/// `tls` are measured on a separate probe connection to the same host.
+/// `ttfb` (until response headers arrive) and `total` (until the body is
+/// read) come from the checked request itself.
+#[derive(Debug, Clone, Default)]
+pub struct TimingBreakdown {
+    pub dns: Option<Duration>,
+    pub connect: Option<Duration>,
+    pub tls: Option<Duration>,
+    pub ttfb: Option<Duration>,
+    pub total: Option<Duration>,
+}
+
+impl TimingBreakdown {
+    /// Measure DNS resolution, TCP connect and, for https, the TLS
+    /// handshake of a fresh connection to the URL's host. Phases that fail
+    /// are left unset
+    pub(crate) fn probe(url: &str, timeout: Duration) -> Self {
+        let mut timing = TimingBreakdown::default();
+        let Ok(url) = Url::parse(url) else {
+            return timing;
+        };
+        let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+            return timing;
+        };
+
+        let start = Instant::now();
+        let Some(addr) = (host, port).to_socket_addrs().ok().and_then(|mut a| a.next()) else {
+            return timing;
+        };
+        timing.dns = Some(start.elapsed());
+
+        let start = Instant::now();
+        let Ok(stream) = TcpStream::connect_timeout(&addr, timeout) else {
+            return timing;
+        };
+        timing.connect = Some(start.elapsed());
+
+        if url.scheme() == "https" {
+            let _ = stream.set_read_timeout(Some(timeout));
+            let _ = stream.set_write_timeout(Some(timeout));
+            let start = Instant::now();
+            if tls::handshake(host, stream).is_ok() {
+                timing.tls = Some(start.elapsed());
+            }
+        }
+
+        timing
+    }
+
+    /// Phases as a JSON object in `unit`. Milliseconds keep microsecond
+    /// precision, phases are often shorter than a millisecond
+    pub fn to_json_string(&self, unit: TimeUnit) -> String {
+        let time = |phase: Option<Duration>| {
+            phase.map_or("null".to_string(), |d| match unit {
+                TimeUnit::Millis => format!("{:.3}", d.as_secs_f64() * 1000.0),
+                _ => unit.format(d),
+            })
+        };
+        let suffix = unit.suffix();
+        format!(
+            "{{\"dns_{suffix}\": {}, \"connect_{suffix}\": {}, \"tls_{suffix}\": {}, \"ttfb_{suffix}\": {}, \"total_{suffix}\": {}}}",
+            time(self.dns),
+            time(self.connect),
+            time(self.tls),
+            time(self.ttfb),
+            time(self.total)
+        )
+    }
+}
@@ -0,0 +1,203 @@
+use std::{
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use openssl::{
+    asn1::Asn1Time,
+    ssl::{SslConnector, SslMethod, SslStream, SslVerifyMode},
+};
+use reqwest::Url;
+
+use crate::{status::escape_json, WebsiteStatus};
+
+/// TLS protocol versions in ascending order, as reported by OpenSSL
+const VERSIONS: [&str; 5] = ["SSLv3", "TLSv1.0", "TLSv1.1", "TLSv1.2", "TLSv1.3"];
+
+/// Normalize a user supplied version ("1.2", "tls1.2", "TLSv1.2") to the
+/// canonical name used in reports
+pub fn parse_version(input: &str) -> Option<&'static str> {
+    let lower = input.to_ascii_lowercase();
+    let number = lower
+        .trim_start_matches("tlsv")
+        .trim_start_matches("tls");
+    match number {
+        "1" | "1.0" => Some("TLSv1.0"),
+        "1.1" => Some("TLSv1.1"),
+        "1.2" => Some("TLSv1.2"),
+        "1.3" => Some("TLSv1.3"),
+        "sslv3" | "ssl3" => Some("SSLv3"),
+        _ => None,
+    }
+}
+
+/// Position of a version in `VERSIONS`, unknown versions sort lowest
+pub fn version_rank(version: &str) -> usize {
+    VERSIONS.iter().position(|v| *v == version).unwrap_or(0)
+}
+
+/// What a standalone handshake revealed about a server
+pub struct TlsDetails {
+    /// Negotiated protocol version, e.g. `TLSv1.3`
+    pub version: String,
+    /// Whole days until the leaf certificate expires, negative once expired
+    pub cert_expires_in_days: Option<i64>,
+    /// Expiry of the leaf certificate as OpenSSL prints it, e.g.
+    /// `Jun  1 12:00:00 2025 GMT`
+    pub cert_not_after: Option<String>,
+}
+
+/// Perform a standalone handshake with the host of an https URL and report
+/// the negotiated protocol version and certificate lifetime
+pub fn inspect(url: &str, timeout: Duration) -> Result<TlsDetails, String> {
+    let url = Url::parse(url).map_err(|e| e.to_string())?;
+    let host = url.host_str().ok_or("URL has no host")?;
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    let addr = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or("failed to resolve domain")?;
+    let stream = TcpStream::connect_timeout(&addr, timeout).map_err(|e| e.to_string())?;
+    stream.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+
+    let stream = handshake(host, stream)?;
+    let ssl = stream.ssl();
+
+    let version = match ssl.version_str() {
+        "TLSv1" => "TLSv1.0".to_string(),
+        other => other.to_string(),
+    };
+    let (cert_expires_in_days, cert_not_after) = match ssl.peer_certificate() {
+        Some(cert) => {
+            let now = Asn1Time::days_from_now(0).map_err(|e| e.to_string())?;
+            let days = i64::from(now.diff(cert.not_after()).map_err(|e| e.to_string())?.days);
+            (Some(days), Some(cert.not_after().to_string()))
+        }
+        None => (None, None),
+    };
+
+    Ok(TlsDetails { version, cert_expires_in_days, cert_not_after })
+}
+
+/// Run a TLS handshake over an established connection.
+///
+/// The connector accepts every protocol version and skips certificate
+/// verification, the goal is to see what the server negotiates, not to
+/// decide whether it is trusted.
+pub(crate) fn handshake(host: &str, stream: TcpStream) -> Result<SslStream<TcpStream>, String> {
+    let mut builder = SslConnector::builder(SslMethod::tls()).map_err(|e| e.to_string())?;
+    builder.set_min_proto_version(None).map_err(|e| e.to_string())?;
+    builder.set_security_level(0);
+    builder.set_verify(SslVerifyMode::NONE);
+    let connector = builder.build();
+
+    connector
+        .configure()
+        .map_err(|e| e.to_string())?
+        .verify_hostname(false)
+        .connect(host, stream)
+        .map_err(|e| e.to_string())
+}
+
+/// Negotiated TLS versions across a scan, checked against a policy minimum
+pub struct TlsCompliance {
+    pub min_version: String,
+    /// Endpoint count per negotiated version, newest version first
+    pub counts: Vec<(String, usize)>,
+    /// URL and negotiated version of every endpoint below the minimum
+    pub below_minimum: Vec<(String, String)>,
+}
+
+impl TlsCompliance {
+    pub fn to_json_string(&self) -> String {
+        format!(
+            r#"{{
+        "min_version": "{}",
+        "versions": {{{}}},
+        "below_minimum": [{}]
+    }}"#,
+            self.min_version,
+            self.counts.iter()
+                .map(|(version, count)| format!("\"{}\": {}", version, count))
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.below_minimum.iter()
+                .map(|(url, version)| format!(
+                    "{{\"url\": \"{}\", \"tls_version\": \"{}\"}}",
+                    escape_json(url),
+                    version
+                ))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+/// Count negotiated TLS versions and collect endpoints below the policy minimum
+pub fn compliance(results: &[WebsiteStatus], min_version: &str) -> TlsCompliance {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    let mut below_minimum = Vec::new();
+
+    for result in results {
+        let version = match &result.tls_version {
+            Some(Ok(version)) => version.clone(),
+            Some(Err(_)) => "handshake failed".to_string(),
+            None => continue,
+        };
+
+        match counts.iter_mut().find(|(v, _)| *v == version) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((version.clone(), 1)),
+        }
+
+        if matches!(result.tls_version, Some(Ok(_)))
+            && version_rank(&version) < version_rank(min_version)
+        {
+            below_minimum.push((result.url.clone(), version));
+        }
+    }
+
+    counts.sort_by(|a, b| version_rank(&b.0).cmp(&version_rank(&a.0)).then(a.0.cmp(&b.0)));
+    TlsCompliance {
+        min_version: min_version.to_string(),
+        counts,
+        below_minimum,
+    }
+}
+
+/// An https endpoint whose certificate expires soon
+pub struct ExpiringCertificate {
+    pub url: String,
+    pub not_after: String,
+    pub days: i64,
+}
+
+impl ExpiringCertificate {
+    pub fn to_json_string(&self) -> String {
+        format!(
+            "{{\"url\": \"{}\", \"cert_not_after\": \"{}\", \"cert_expires_in_days\": {}}}",
+            escape_json(&self.url),
+            escape_json(&self.not_after),
+            self.days
+        )
+    }
+}
+
+/// Endpoints whose certificate expires in fewer than `window` days, soonest first
+pub fn expiring_within(results: &[WebsiteStatus], window: i64) -> Vec<ExpiringCertificate> {
+    let mut expiring: Vec<_> = results.iter()
+        .filter_map(|result| {
+            let days = result.cert_expires_in_days.filter(|days| *days < window)?;
+            Some(ExpiringCertificate {
+                url: result.url.clone(),
+                not_after: result.cert_not_after.clone().unwrap_or_else(|| "unknown".to_string()),
+                days,
+            })
+        })
+        .collect();
+    expiring.sort_by_key(|cert| cert.days);
+    expiring
+}
@@ -0,0 +1,42 @@
+//! Notifications posted to an HTTP endpoint when a URL changes state
+//! between interval rounds.
+
+use std::time::Duration;
+
+use reqwest::blocking::Client;
+
+use crate::{status::escape_json, TimeUnit, TimestampFormat, WebsiteStatus};
+
+/// Payload announcing that a URL is healthy again:
+/// `{"event": "recovered", "url": ..., "previous": {...}, "current": {...}}`
+/// with both results as they appear in the results file
+pub fn recovery_payload(
+    previous: &WebsiteStatus,
+    current: &WebsiteStatus,
+    timestamps: TimestampFormat,
+    unit: TimeUnit,
+) -> String {
+    format!(
+        "{{\"event\": \"recovered\", \"url\": \"{}\", \"previous\": {}, \"current\": {}}}",
+        escape_json(&current.url),
+        previous.to_json_string(timestamps, unit),
+        current.to_json_string(timestamps, unit)
+    )
+}
+
+/// POST a JSON payload, failing on transport errors and non-2xx replies
+pub fn post(endpoint: &str, payload: String, timeout: Duration) -> Result<(), String> {
+    let client = Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client.post(endpoint)
+        .header("Content-Type", "application/json")
+        .body(payload)
+        .send()
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status().as_u16()));
+    }
+    Ok(())
+}
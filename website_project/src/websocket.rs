@@ -0,0 +1,60 @@
+use std::{
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use openssl::ssl::{self, SslConnector, SslMethod};
+use tungstenite::{
+    client::IntoClientRequest,
+    handshake::{client::Request, HandshakeError},
+    http::{HeaderName, HeaderValue},
+};
+
+/// Perform the WebSocket opening handshake with a `ws://` or `wss://` URL,
+/// sending `headers` with the upgrade request, then close the connection.
+/// Returns the status of the handshake response, `101` on success
+pub fn handshake(url: &str, headers: &[(String, String)], timeout: Duration) -> Result<u16, String> {
+    let mut request = url.into_client_request().map_err(|e| e.to_string())?;
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| e.to_string())?;
+        let value = HeaderValue::from_str(value).map_err(|e| e.to_string())?;
+        request.headers_mut().insert(name, value);
+    }
+
+    let uri = request.uri();
+    let host = uri.host().ok_or("missing host")?.trim_matches(['[', ']']);
+    let port = uri.port_u16().unwrap_or(if uri.scheme_str() == Some("wss") { 443 } else { 80 });
+    let addr = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or("failed to resolve domain")?;
+    let stream = TcpStream::connect_timeout(&addr, timeout).map_err(|e| e.to_string())?;
+    stream.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+
+    if uri.scheme_str() != Some("wss") {
+        return upgrade(request, stream);
+    }
+    // Verified like https checks, unlike the TLS inspection connector
+    let connector = SslConnector::builder(SslMethod::tls()).map_err(|e| e.to_string())?.build();
+    let host = host.to_string();
+    let stream = connector.connect(&host, stream).map_err(|e| match e {
+        ssl::HandshakeError::WouldBlock(_) => "timed out waiting for TLS handshake".to_string(),
+        e => e.to_string(),
+    })?;
+    upgrade(request, stream)
+}
+
+/// Send the upgrade request over an open connection and read the response
+fn upgrade<S: Read + Write>(request: Request, stream: S) -> Result<u16, String> {
+    let (mut socket, response) = tungstenite::client(request, stream).map_err(|e| match e {
+        HandshakeError::Failure(e) => e.to_string(),
+        HandshakeError::Interrupted(_) => "timed out waiting for handshake".to_string(),
+    })?;
+    // Only the handshake is checked, a failed close is not the server's fault
+    let _ = socket.close(None);
+    let _ = socket.flush();
+    Ok(response.status().as_u16())
+}